@@ -0,0 +1,7 @@
+// crates/examples/readme/src/read.rs
+use sheller::new;
+
+fn main() {
+    let stdout = new!("echo hello").read();
+    assert_eq!(stdout, "hello");
+}
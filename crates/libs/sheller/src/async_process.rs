@@ -0,0 +1,210 @@
+//! Runtime-agnostic async execution on top of `async-process`.
+//!
+//! Enabled by the `async-process` feature.
+//! It works on any executor such as `smol` or `async-std`.
+
+use crate::{exec, Output, Result, Sheller};
+use ::async_process::{Child, ChildStderr, ChildStdout, Command};
+use async_io::Timer;
+use futures_lite::{AsyncRead, AsyncReadExt};
+use std::{
+    ffi::OsStr,
+    future::{Future, IntoFuture},
+    io,
+    path::Path,
+    pin::Pin,
+    process::{ExitStatus, Stdio},
+    time::Duration,
+};
+
+/// Await a `Sheller` to run it on any executor.
+///
+/// The result is the same as `Sheller::try_run`, and the timeout, the process group option and the output options such as tee, line callbacks and logging output apply as they do to it.
+/// If the future is dropped before it completes, the command is killed, together with its whole process group if it was spawned in one.
+///
+/// # Examples
+/// ```
+/// use sheller::Sheller;
+///
+/// futures_lite::future::block_on(async {
+///     Sheller::new("echo hello").await.unwrap();
+/// });
+/// ```
+impl IntoFuture for Sheller {
+    type Output = Result<()>;
+    type IntoFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        let (mut command, options) = self.into_parts();
+        exec::prepare(&mut command, &options);
+        let mut command = Command::from(command);
+        command.kill_on_drop(true);
+        Box::pin(async move {
+            exec::run_async::<AsyncProcess>(&mut command, &options).await?;
+            Ok(())
+        })
+    }
+}
+
+/// Extension of `async_process::Command` with the same behavior as `CommandExt`.
+pub trait AsyncCommandExt {
+    /// Run the command asynchronously and panic if the command failed to run.
+    ///
+    /// # Examples
+    /// ```
+    /// use async_process::Command;
+    /// use sheller::async_process::AsyncCommandExt;
+    ///
+    /// futures_lite::future::block_on(async {
+    ///     #[cfg(windows)]
+    ///     Command::new("cmd.exe")
+    ///         .args(["/D", "/S", "/C", "echo hello"])
+    ///         .run_async()
+    ///         .await;
+    ///
+    ///     #[cfg(unix)]
+    ///     Command::new("echo").arg("hello").run_async().await;
+    /// });
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the command failed to run.
+    fn run_async(&mut self) -> impl Future<Output = ()> + Send;
+
+    /// Run the command asynchronously and return a `Result`.
+    ///
+    /// # Examples
+    /// ```
+    /// use async_process::Command;
+    /// use sheller::async_process::AsyncCommandExt;
+    ///
+    /// futures_lite::future::block_on(async {
+    ///     #[cfg(windows)]
+    ///     Command::new("cmd.exe")
+    ///         .args(["/D", "/S", "/C", "echo hello"])
+    ///         .try_run_async()
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     #[cfg(unix)]
+    ///     Command::new("echo")
+    ///         .arg("hello")
+    ///         .try_run_async()
+    ///         .await
+    ///         .unwrap();
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run.
+    fn try_run_async(&mut self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Run the command asynchronously, capture its stdout and stderr, and return an `Output`.
+    ///
+    /// stdout and stderr are always piped, and stdin is left as it is configured.
+    ///
+    /// # Examples
+    /// ```
+    /// use async_process::Command;
+    /// use sheller::{async_process::AsyncCommandExt, Sheller};
+    ///
+    /// futures_lite::future::block_on(async {
+    ///     let mut command = Command::from(Sheller::new("echo hello").build());
+    ///     let output = command.output_async().await.unwrap();
+    ///     assert_eq!(output.stdout_string().unwrap(), "hello");
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run.
+    fn output_async(&mut self) -> impl Future<Output = Result<Output>> + Send;
+}
+
+impl AsyncCommandExt for Command {
+    async fn run_async(&mut self) {
+        self.try_run_async().await.unwrap();
+    }
+
+    async fn try_run_async(&mut self) -> Result<()> {
+        exec::run_async::<AsyncProcess>(self, &exec::Options::default()).await?;
+        Ok(())
+    }
+
+    async fn output_async(&mut self) -> Result<Output> {
+        exec::output_async::<AsyncProcess>(self, &exec::Options::default()).await
+    }
+}
+
+/// Any runtime, which async execution runs on through the primitives of `async-process`.
+struct AsyncProcess;
+
+impl exec::Runtime for AsyncProcess {
+    type Command = Command;
+    type Child = Child;
+    type Stdout = ChildStdout;
+    type Stderr = ChildStderr;
+
+    fn program(command: &Command) -> &OsStr {
+        command.get_program()
+    }
+
+    fn current_dir(command: &Command) -> Option<&Path> {
+        command.get_current_dir()
+    }
+
+    fn spawn(command: &mut Command) -> io::Result<Child> {
+        command.spawn()
+    }
+
+    fn id(child: &Child) -> Option<u32> {
+        Some(child.id())
+    }
+
+    fn take_stdout(child: &mut Child) -> Option<ChildStdout> {
+        child.stdout.take()
+    }
+
+    fn take_stderr(child: &mut Child) -> Option<ChildStderr> {
+        child.stderr.take()
+    }
+
+    fn read_stdout(stdout: ChildStdout) -> impl Future<Output = io::Result<Vec<u8>>> + Send {
+        read_to_end(stdout)
+    }
+
+    fn read_stderr(stderr: ChildStderr) -> impl Future<Output = io::Result<Vec<u8>>> + Send {
+        read_to_end(stderr)
+    }
+
+    fn wait(child: &mut Child) -> impl Future<Output = io::Result<ExitStatus>> + Send {
+        child.status()
+    }
+
+    fn start_kill(child: &mut Child) -> io::Result<()> {
+        child.kill()
+    }
+
+    async fn sleep(duration: Duration) {
+        Timer::after(duration).await;
+    }
+}
+
+impl exec::SetStdio for Command {
+    fn set_stdin(&mut self, stdio: Stdio) {
+        self.stdin(stdio);
+    }
+
+    fn set_stdout(&mut self, stdio: Stdio) {
+        self.stdout(stdio);
+    }
+
+    fn set_stderr(&mut self, stdio: Stdio) {
+        self.stderr(stdio);
+    }
+}
+
+async fn read_to_end(mut reader: impl AsyncRead + Unpin) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    reader.read_to_end(&mut buffer).await?;
+    Ok(buffer)
+}
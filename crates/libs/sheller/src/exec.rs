@@ -0,0 +1,1622 @@
+use crate::{
+    logging::{self, event},
+    Context, Error, Level, LogLevels, Output, Result, Signal,
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Write},
+    path::PathBuf,
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex, PoisonError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+use tracing::Instrument;
+use tracing::{dispatcher, Dispatch, Span};
+
+/// The default time between the graceful and the forced termination of a timed out command.
+pub(crate) const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The number of lines of captured stderr that are attached to an error, and of captured output that an error displays.
+pub(crate) const STDERR_TAIL_LINES: usize = 20;
+
+/// Options that change how a command is run, on top of what `std::process::Command` holds.
+#[derive(Clone)]
+pub(crate) struct Options {
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) grace_period: Duration,
+    pub(crate) process_group: bool,
+    /// The exit codes that count as success.
+    pub(crate) ok_codes: Vec<i32>,
+    pub(crate) on_stdout_line: Option<LineCallback>,
+    pub(crate) on_stderr_line: Option<LineCallback>,
+    /// The levels to log the lines of stdout and stderr at, if they are forwarded to `tracing`.
+    pub(crate) log_output: Option<OutputLevels>,
+    /// The levels to log the start, the success and the failure at, instead of the global ones.
+    pub(crate) log_levels: Option<LogLevels>,
+    pub(crate) secrets: Secrets,
+    /// The redirections of the standard streams, in the order they were added.
+    pub(crate) redirects: Vec<Redirect>,
+    /// Whether stdout and stderr are written to the terminal and captured at the same time.
+    pub(crate) tee: Option<Tee>,
+    /// Whether stdout and stderr are held back together, and written to stderr only if the command fails.
+    pub(crate) quiet_unless_failed: bool,
+    /// Why the command must not be spawned, if it could not be built as it was asked for.
+    pub(crate) invalid: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Tee {
+    /// The number of bytes at the end of each stream that are kept, or `None` to keep all of them.
+    pub(crate) limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct OutputLevels {
+    pub(crate) stdout: Level,
+    pub(crate) stderr: Level,
+}
+
+impl Options {
+    /// Returns `code` if it counts as success.
+    pub(crate) fn accept(&self, code: i32) -> Option<i32> {
+        self.ok_codes.contains(&code).then_some(code)
+    }
+
+    pub(crate) fn log_levels(&self) -> LogLevels {
+        self.log_levels.unwrap_or_else(logging::log_levels)
+    }
+
+    /// Returns `command` with the secrets replaced when it is formatted with `Debug`.
+    pub(crate) fn redact<'a>(&'a self, command: &'a dyn std::fmt::Debug) -> Redacted<'a> {
+        Redacted {
+            command,
+            secrets: &self.secrets,
+            redirects: &self.redirects,
+        }
+    }
+
+    /// Returns the error to fail with instead of spawning the command, if it could not be built as it was asked for.
+    pub(crate) fn check_valid(&self) -> std::io::Result<()> {
+        match &self.invalid {
+            Some(invalid) => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                invalid.clone(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the input that stdin is written from, if the last redirection of stdin is one.
+    pub(crate) fn stdin_input(&self) -> Option<&Input> {
+        self.redirects
+            .iter()
+            .rev()
+            .find_map(|redirect| match redirect {
+                Redirect::StdinFromFile(_) => Some(None),
+                Redirect::StdinFrom(input) => Some(Some(input)),
+                _ => None,
+            })
+            .flatten()
+    }
+
+    /// Returns `true` if the output is piped to be teed or held back, whichever stream it comes from.
+    fn pipes_output(&self) -> bool {
+        self.log_output.is_some() || self.tee.is_some() || self.quiet_unless_failed
+    }
+
+    /// Returns `true` if stdout is piped for line callbacks, logging, tee or holding it back.
+    pub(crate) fn pipes_stdout(&self) -> bool {
+        self.on_stdout_line.is_some() || self.pipes_output()
+    }
+
+    /// Returns `true` if stderr is piped for line callbacks, logging, tee or holding it back.
+    fn pipes_stderr(&self) -> bool {
+        self.on_stderr_line.is_some() || self.pipes_output()
+    }
+
+    /// Pipe the streams whose lines are passed to a callback, logged, teed or held back.
+    pub(crate) fn pipe_line_streams(&self, command: &mut Command) {
+        if self.pipes_stdout() {
+            command.stdout(Stdio::piped());
+        }
+        if self.pipes_stderr() {
+            command.stderr(Stdio::piped());
+        }
+    }
+}
+
+impl std::fmt::Debug for Options {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let redirects: Vec<_> = self
+            .redirects
+            .iter()
+            .map(|redirect| Rendered(self.secrets.redact(&redirect.to_string())))
+            .collect();
+        f.debug_struct("Options")
+            .field("timeout", &self.timeout)
+            .field("grace_period", &self.grace_period)
+            .field("process_group", &self.process_group)
+            .field("ok_codes", &self.ok_codes)
+            .field("on_stdout_line", &self.on_stdout_line)
+            .field("on_stderr_line", &self.on_stderr_line)
+            .field("log_output", &self.log_output)
+            .field("log_levels", &self.log_levels)
+            .field("secrets", &self.secrets)
+            .field("redirects", &redirects)
+            .field("tee", &self.tee)
+            .field("quiet_unless_failed", &self.quiet_unless_failed)
+            .field("invalid", &self.invalid)
+            .finish()
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            grace_period: DEFAULT_GRACE_PERIOD,
+            process_group: false,
+            ok_codes: vec![0],
+            on_stdout_line: None,
+            on_stderr_line: None,
+            log_output: None,
+            log_levels: None,
+            secrets: Secrets::default(),
+            redirects: Vec::new(),
+            tee: None,
+            quiet_unless_failed: false,
+            invalid: None,
+        }
+    }
+}
+
+/// The text that a secret is replaced with.
+pub(crate) const REDACTED: &str = "***";
+
+/// Values that are replaced with `***` wherever a command, its output or its error is logged or rendered.
+#[derive(Clone, Default)]
+pub(crate) struct Secrets(Vec<String>);
+
+impl Secrets {
+    /// Add `value`, and the escaped form it takes in the `Debug` output of a command.
+    pub(crate) fn add(&mut self, value: &str) {
+        let escaped = value.escape_debug().to_string();
+        for value in [value.to_string(), escaped] {
+            if !value.is_empty() && !self.0.contains(&value) {
+                self.0.push(value);
+            }
+        }
+        // Replace the longest first, so that a secret that contains another is replaced as a whole.
+        self.0.sort_by_key(|value| std::cmp::Reverse(value.len()));
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn redact(&self, text: &str) -> String {
+        self.0.iter().fold(text.to_string(), |text, secret| {
+            text.replace(secret, REDACTED)
+        })
+    }
+
+    /// Returns `value` with the secrets replaced when it is formatted with `Debug`, for a value that is not a whole command.
+    pub(crate) fn redact_debug<'a>(&'a self, value: &'a dyn std::fmt::Debug) -> Redacted<'a> {
+        Redacted {
+            command: value,
+            secrets: self,
+            redirects: &[],
+        }
+    }
+}
+
+impl std::fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|_| REDACTED))
+            .finish()
+    }
+}
+
+/// A value whose `Debug` output has the secrets replaced, and the redirections appended if it is a command.
+pub(crate) struct Redacted<'a> {
+    command: &'a dyn std::fmt::Debug,
+    secrets: &'a Secrets,
+    redirects: &'a [Redirect],
+}
+
+impl std::fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use std::fmt::Write;
+
+        if self.secrets.is_empty() && self.redirects.is_empty() {
+            return self.command.fmt(f);
+        }
+        let mut rendered = format!("{:?}", self.command);
+        for redirect in self.redirects {
+            write!(rendered, " {redirect}")?;
+        }
+        f.write_str(&self.secrets.redact(&rendered))
+    }
+}
+
+/// A redirection of a standard stream of a command, applied when the command is spawned.
+#[derive(Debug, Clone)]
+pub(crate) enum Redirect {
+    StdoutToFile { path: PathBuf, append: bool },
+    StderrToStdout,
+    StdinFromFile(PathBuf),
+    StdinFrom(Input),
+}
+
+impl std::fmt::Display for Redirect {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::StdoutToFile {
+                path,
+                append: false,
+            } => write!(f, "> \"{}\"", path.display()),
+            Self::StdoutToFile { path, append: true } => write!(f, ">> \"{}\"", path.display()),
+            Self::StderrToStdout => write!(f, "2>&1"),
+            Self::StdinFromFile(path) => write!(f, "< \"{}\"", path.display()),
+            Self::StdinFrom(Input::Bytes(bytes)) => write!(f, "< [{} bytes]", bytes.len()),
+            Self::StdinFrom(Input::Reader(_)) => write!(f, "< [reader]"),
+            Self::StdinFrom(Input::Lines(_)) => write!(f, "< [lines]"),
+        }
+    }
+}
+
+/// What is written to stdin of a command while it runs.
+#[derive(Clone)]
+pub(crate) enum Input {
+    Bytes(Vec<u8>),
+    Reader(TakeOnce<dyn Read + Send>),
+    /// Lines that are written with a `\n` after each.
+    Lines(TakeOnce<dyn Iterator<Item = String> + Send>),
+}
+
+impl std::fmt::Debug for Input {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Bytes(bytes) => f
+                .debug_tuple("Bytes")
+                .field(&format_args!("{} bytes", bytes.len()))
+                .finish(),
+            Self::Reader(_) => f.write_str("Reader"),
+            Self::Lines(_) => f.write_str("Lines"),
+        }
+    }
+}
+
+impl Input {
+    /// Write the input to `stdin`, and close it.
+    fn write_to(self, stdin: impl Write) -> std::io::Result<()> {
+        let mut stdin = BufWriter::new(stdin);
+        match self {
+            Self::Bytes(bytes) => stdin.write_all(&bytes)?,
+            Self::Reader(reader) => {
+                if let Some(mut reader) = reader.take() {
+                    std::io::copy(&mut reader, &mut stdin)?;
+                }
+            }
+            Self::Lines(lines) => {
+                for line in lines.take().into_iter().flatten() {
+                    stdin.write_all(line.as_bytes())?;
+                    stdin.write_all(b"\n")?;
+                }
+            }
+        }
+        stdin.flush()
+    }
+}
+
+/// A value that is moved out by the first run of a command, for the inputs that can only be read once.
+pub(crate) struct TakeOnce<T: ?Sized>(Arc<Mutex<Option<Box<T>>>>);
+
+impl<T: ?Sized> TakeOnce<T> {
+    pub(crate) fn new(value: Box<T>) -> Self {
+        Self(Arc::new(Mutex::new(Some(value))))
+    }
+
+    fn take(&self) -> Option<Box<T>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).take()
+    }
+}
+
+impl<T: ?Sized> Clone for TakeOnce<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<T: ?Sized> std::fmt::Debug for TakeOnce<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("TakeOnce")
+    }
+}
+
+/// Open the file that stdout is redirected to.
+pub(crate) fn open_stdout(path: &std::path::Path, append: bool) -> std::io::Result<File> {
+    File::options()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+}
+
+/// Returns the read end of a pipe that `bytes` are written to from a thread.
+///
+/// The command may exit without reading all of its input, so a broken pipe is not an error.
+pub(crate) fn feed(bytes: Vec<u8>) -> std::io::Result<std::io::PipeReader> {
+    let (reader, mut writer) = std::io::pipe()?;
+    thread::spawn(move || {
+        if let Err(e) = writer.write_all(&bytes) {
+            if e.kind() != std::io::ErrorKind::BrokenPipe {
+                event!(Level::Debug, error = ?e, "Failed to write stdin of command.");
+            }
+        }
+    });
+    Ok(reader)
+}
+
+/// A command whose standard streams can be set, which the commands of the async runtimes are as well as `std::process::Command`.
+pub(crate) trait SetStdio {
+    fn set_stdin(&mut self, stdio: Stdio);
+    fn set_stdout(&mut self, stdio: Stdio);
+    fn set_stderr(&mut self, stdio: Stdio);
+}
+
+impl SetStdio for Command {
+    fn set_stdin(&mut self, stdio: Stdio) {
+        self.stdin(stdio);
+    }
+
+    fn set_stdout(&mut self, stdio: Stdio) {
+        self.stdout(stdio);
+    }
+
+    fn set_stderr(&mut self, stdio: Stdio) {
+        self.stderr(stdio);
+    }
+}
+
+/// Apply the redirections of `options` to `command` in order.
+///
+/// stdin is piped if it is written from an `Input`, which `Streams::start` starts writing.
+///
+/// If stderr is redirected to a stdout that is piped, both are written to a new pipe, and its read end is returned to be read as stdout.
+fn redirect(
+    command: &mut impl SetStdio,
+    options: &Options,
+    piped_stdout: bool,
+) -> std::io::Result<Option<std::io::PipeReader>> {
+    let mut stdout = None;
+    let mut merged = None;
+    for redirect in &options.redirects {
+        match redirect {
+            Redirect::StdoutToFile { path, append } => {
+                let file = open_stdout(path, *append)?;
+                command.set_stdout(file.try_clone()?.into());
+                stdout = Some(file);
+            }
+            Redirect::StderrToStdout => match &stdout {
+                Some(file) => {
+                    command.set_stderr(file.try_clone()?.into());
+                }
+                None if piped_stdout => {
+                    let (reader, writer) = std::io::pipe()?;
+                    command.set_stdout(writer.try_clone()?.into());
+                    command.set_stderr(writer.into());
+                    merged = Some(reader);
+                }
+                None => {
+                    command.set_stderr(std::io::stdout().into());
+                }
+            },
+            Redirect::StdinFromFile(path) => {
+                command.set_stdin(File::open(path)?.into());
+            }
+            Redirect::StdinFrom(_) => {
+                command.set_stdin(Stdio::piped());
+            }
+        }
+    }
+    Ok(merged)
+}
+
+/// Make the child write stderr to where its stdout goes, once both are set up, like `2>&1`.
+#[cfg(unix)]
+pub(crate) fn stderr_to_stdout(command: &mut Command) {
+    let dup = || {
+        // SAFETY: `dup2` is async-signal-safe, and only changes the file descriptors of the child.
+        if unsafe { libc::dup2(1, 2) } == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    };
+    // SAFETY: the closure only calls `dup2`, which is safe to call between `fork` and `exec`.
+    unsafe {
+        std::os::unix::process::CommandExt::pre_exec(command, dup);
+    }
+}
+
+/// Make the child write stderr to stdout of this process, because where the stdout of a command goes is not known on Windows.
+#[cfg(windows)]
+pub(crate) fn stderr_to_stdout(command: &mut Command) {
+    command.stderr(std::io::stdout());
+}
+
+/// A callback that receives each line of a stream, without the line ending.
+///
+/// It is shared with the reader thread that calls it.
+#[derive(Clone)]
+pub(crate) struct LineCallback(Arc<Mutex<LineFn>>);
+
+type LineFn = dyn FnMut(&str) + Send;
+
+impl LineCallback {
+    pub(crate) fn new<F>(callback: F) -> Self
+    where
+        F: FnMut(&str) + Send + 'static,
+    {
+        Self(Arc::new(Mutex::new(callback)))
+    }
+
+    fn call(&self, line: &str) {
+        let mut callback = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        callback(line);
+    }
+}
+
+impl std::fmt::Debug for LineCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("LineCallback")
+    }
+}
+
+/// Where the lines of a stream go while it is read.
+struct LineSink {
+    stream: &'static str,
+    callback: Option<LineCallback>,
+    /// The level to log the lines at, and the span to log them in.
+    log: Option<(Level, Span)>,
+    secrets: Secrets,
+    /// Whether the stream is written to the same stream of this process as it is read.
+    echo: bool,
+    /// The output of both streams in the order it was read, if it is held back.
+    held: Option<Held>,
+}
+
+type Held = Arc<Mutex<Vec<u8>>>;
+
+impl LineSink {
+    fn is_empty(&self) -> bool {
+        self.callback.is_none() && self.log.is_none()
+    }
+
+    /// Write `bytes` to the same stream of this process, if the stream is teed.
+    fn echo(&self, bytes: &[u8]) {
+        if !self.echo {
+            return;
+        }
+        // The output of the command is best effort, like it is when the stream is inherited.
+        let _ = if self.stream == "stdout" {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(bytes).and_then(|()| stdout.flush())
+        } else {
+            std::io::stderr().write_all(bytes)
+        };
+    }
+
+    /// Add `bytes` to the output that is held back, if it is.
+    fn hold(&self, bytes: &[u8]) {
+        if let Some(held) = &self.held {
+            held.lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .extend_from_slice(bytes);
+        }
+    }
+
+    fn send(&self, line: &str) {
+        if let Some(callback) = &self.callback {
+            callback.call(line);
+        }
+        if let Some((level, span)) = &self.log {
+            let line = self.secrets.redact(line);
+            span.in_scope(|| logging::output_line(*level, self.stream, &line));
+        }
+    }
+}
+
+type Reader = JoinHandle<std::io::Result<Vec<u8>>>;
+
+type Writer = JoinHandle<std::io::Result<()>>;
+
+/// The threads that write stdin and read the piped streams of a child.
+#[derive(Debug)]
+pub(crate) struct Streams {
+    stdin: Option<Writer>,
+    stdout: Option<Reader>,
+    stderr: Option<Reader>,
+    /// The output of both streams in the order it was read, if it is held back.
+    held: Option<Held>,
+    /// Whether all of stdout and stderr is kept, as it is for `try_output`.
+    captured: bool,
+}
+
+/// What was kept of the streams of a child once they were closed.
+#[derive(Debug, Default)]
+pub(crate) struct Joined {
+    pub(crate) stdout: Vec<u8>,
+    pub(crate) stderr: Vec<u8>,
+    /// The output of both streams in the order it was read, if it was held back.
+    pub(crate) held: Vec<u8>,
+}
+
+impl Streams {
+    /// Start writing the input of `child`, and reading its piped streams, keeping the bytes read if `capture` is `true`.
+    ///
+    /// The lines are logged in the current span.
+    ///
+    /// If the output is held back until the command fails, both streams are also kept together, in the order they are read.
+    pub(crate) fn start(child: &mut Child, options: &Options, capture: bool) -> Self {
+        let input = options.stdin_input().cloned();
+        Self {
+            stdin: child
+                .stdin
+                .take()
+                .zip(input)
+                .map(|(w, input)| write_stream(w, input)),
+            ..Self::read(child.stdout.take(), child.stderr.take(), options, capture)
+        }
+    }
+
+    /// Start reading `stdout` and `stderr` as `start` does, without writing any input.
+    fn read<O, E>(stdout: Option<O>, stderr: Option<E>, options: &Options, capture: bool) -> Self
+    where
+        O: Read + Send + 'static,
+        E: Read + Send + 'static,
+    {
+        let held = options
+            .quiet_unless_failed
+            .then(|| Arc::new(Mutex::new(Vec::new())));
+        let captured = capture;
+        let capture = match options.tee {
+            _ if capture => Capture::All,
+            Some(Tee { limit: Some(limit) }) => Capture::Last(limit),
+            Some(Tee { limit: None }) => Capture::All,
+            None => Capture::Nothing,
+        };
+        let log = |level: fn(OutputLevels) -> Level| {
+            options.log_output.map(level).zip(Some(Span::current()))
+        };
+        let stdout_sink = LineSink {
+            stream: "stdout",
+            callback: options.on_stdout_line.clone(),
+            log: log(|levels| levels.stdout),
+            secrets: options.secrets.clone(),
+            echo: options.tee.is_some(),
+            held: held.clone(),
+        };
+        let stderr_sink = LineSink {
+            stream: "stderr",
+            callback: options.on_stderr_line.clone(),
+            log: log(|levels| levels.stderr),
+            secrets: options.secrets.clone(),
+            echo: options.tee.is_some(),
+            held: held.clone(),
+        };
+        Self {
+            stdin: None,
+            stdout: stdout.map(|r| read_stream(r, stdout_sink, capture.clone())),
+            stderr: stderr.map(|r| read_stream(r, stderr_sink, capture)),
+            held,
+            captured,
+        }
+    }
+
+    /// Wait for the streams to be closed, and return what was kept of them.
+    ///
+    /// The command may exit without reading all of its input, so a broken pipe of stdin is not an error.
+    pub(crate) fn join(self) -> std::io::Result<Joined> {
+        let stdout = join(self.stdout)?;
+        let stderr = join(self.stderr)?;
+        if let Some(stdin) = self.stdin {
+            match stdin
+                .join()
+                .map_err(|_| std::io::Error::other("Writer thread panicked."))?
+            {
+                Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => return Err(e),
+                _ => {}
+            }
+        }
+        let held = self.held.map_or_else(Vec::new, |held| {
+            std::mem::take(&mut *held.lock().unwrap_or_else(PoisonError::into_inner))
+        });
+        Ok(Joined {
+            stdout,
+            stderr,
+            held,
+        })
+    }
+
+    /// Wait for the streams of a command that failed before they were joined, such as one that timed out, and report its output with `error`.
+    ///
+    /// A process that the command left running may hold the streams open, so they are waited for during the grace period at most, and left to their threads after it.
+    pub(crate) fn report(self, error: Error, options: &Options) -> Error {
+        let deadline = Instant::now() + options.grace_period;
+        while !self.is_finished() {
+            if Instant::now() >= deadline {
+                event!(
+                    Level::Debug,
+                    "Streams of command were not closed within grace period, leaving them."
+                );
+                return error;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        let captured = self.captured;
+        match self.join() {
+            Ok(joined) if captured => report_output(
+                attach_stderr(error, options, &joined.stderr),
+                options,
+                &joined,
+            ),
+            Ok(joined) => report_output(error, options, &joined),
+            Err(_) => error,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.stdin.as_ref().is_none_or(JoinHandle::is_finished)
+            && self.stdout.as_ref().is_none_or(JoinHandle::is_finished)
+            && self.stderr.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+}
+
+/// A command rendered once for logging, for when the `std::process::Command` is no longer at hand.
+///
+/// Its `Debug` output is the same as the `Debug` output of the command, with the secrets replaced.
+/// The redirections are not included, so that `Options::redact` appends them once when it is reported.
+pub(crate) struct Rendered(String);
+
+impl Rendered {
+    pub(crate) fn new(command: &Command, options: &Options) -> Self {
+        Self(format!("{:?}", options.secrets.redact_debug(command)))
+    }
+}
+
+impl std::fmt::Debug for Rendered {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Run the command to completion, classify its exit status, and return the accepted exit code.
+pub(crate) fn run(command: &mut Command, options: &Options) -> Result<i32> {
+    run_map(command, options, |code| options.accept(code))
+}
+
+/// Run the command to completion, and map its exit code with `map`.
+///
+/// Fails with `Error::ExitCode` if `map` returns `None`.
+pub(crate) fn run_map<T>(
+    command: &mut Command,
+    options: &Options,
+    map: impl FnOnce(i32) -> Option<T>,
+) -> Result<T> {
+    let _span = run_span(command, options).entered();
+    event!(options.log_levels().start, command = ?options.redact(command), "Running command.");
+    options.pipe_line_streams(command);
+    let start = Instant::now();
+    let mut child = spawn(command, options, start, options.pipes_stdout())?;
+    let streams = Streams::start(&mut child, options, false);
+    let status = match wait(command, &mut child, options, start) {
+        Ok(status) => status,
+        Err(e) => return Err(streams.report(e, options)),
+    };
+    let joined = streams
+        .join()
+        .map_err(|e| wait_error(command, options, e, start))?;
+    classify(command, options, status, start.elapsed(), map)
+        .map_err(|e| report_output(e, options, &joined))
+}
+
+/// Run the command to completion with piped stdout and stderr, and classify its exit status.
+pub(crate) fn output(command: &mut Command, options: &Options) -> Result<Output> {
+    let _span = run_span(command, options).entered();
+    event!(options.log_levels().start, command = ?options.redact(command), "Running command.");
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let start = Instant::now();
+    let mut child = spawn(command, options, start, true)?;
+    let streams = Streams::start(&mut child, options, true);
+    let status = match wait(command, &mut child, options, start) {
+        Ok(status) => status,
+        Err(e) => return Err(streams.report(e, options)),
+    };
+    let duration = start.elapsed();
+    let joined = streams
+        .join()
+        .map_err(|e| wait_error(command, options, e, start))?;
+    finish_output(command, options, status, joined, duration)
+}
+
+/// Run the command to completion as `std::process::Command::output` does, and classify its exit status.
+///
+/// stdout and stderr are piped unless they are configured on `command`, which is left as it is, so the options that need the child or its streams do not apply.
+pub(crate) fn output_configured(command: &mut Command, options: &Options) -> Result<Output> {
+    let _span = run_span(command, options).entered();
+    event!(options.log_levels().start, command = ?options.redact(command), "Running command.");
+    let start = Instant::now();
+    let output = command
+        .output()
+        .map_err(|e| spawn_error(command, options, e, start))?;
+    let joined = Joined {
+        stdout: output.stdout,
+        stderr: output.stderr,
+        ..Joined::default()
+    };
+    finish_output(command, options, output.status, joined, start.elapsed())
+}
+
+/// Classify the exit status of a command whose output was captured, and attach the stderr tail to the error.
+pub(crate) fn finish_output(
+    command: &dyn std::fmt::Debug,
+    options: &Options,
+    status: ExitStatus,
+    joined: Joined,
+    duration: Duration,
+) -> Result<Output> {
+    check_status(command, status, duration, options)
+        .map_err(|e| attach_stderr(e, options, &joined.stderr))
+        .map_err(|e| report_output(e, options, &joined))?;
+    Ok(Output {
+        stdout: joined.stdout,
+        stderr: joined.stderr,
+        status,
+        duration,
+    })
+}
+
+/// Attach the tail of the captured stderr to `error`.
+fn attach_stderr(mut error: Error, options: &Options, stderr: &[u8]) -> Error {
+    if let Some(context) = error.context_mut() {
+        context.stderr_tail = Some(options.secrets.redact(&tail(stderr, STDERR_TAIL_LINES)));
+    }
+    error
+}
+
+/// Apply the options that are set on the command itself before it is spawned.
+pub(crate) fn prepare(command: &mut Command, options: &Options) {
+    #[cfg(unix)]
+    if options.process_group {
+        std::os::unix::process::CommandExt::process_group(command, 0);
+    }
+    #[cfg(windows)]
+    let _ = (command, options);
+}
+
+/// Report the output of a failed command: write it to stderr after `error` if it was held back, or attach it to `error` if it was teed.
+pub(crate) fn report_output(mut error: Error, options: &Options, joined: &Joined) -> Error {
+    if options.quiet_unless_failed {
+        let output = options
+            .secrets
+            .redact(&String::from_utf8_lossy(&joined.held));
+        let mut terminal = std::io::stderr().lock();
+        // The output of the command is best effort, like it is when the stream is inherited.
+        let _ = writeln!(terminal, "{error}").and_then(|()| terminal.write_all(output.as_bytes()));
+    }
+    let (Some(tee), Some(context)) = (options.tee, error.context_mut()) else {
+        return error;
+    };
+    let teed = |bytes: &[u8]| {
+        let start = tee
+            .limit
+            .map_or(0, |limit| bytes.len().saturating_sub(limit));
+        options
+            .secrets
+            .redact(&String::from_utf8_lossy(&bytes[start..]))
+    };
+    context.stdout_tail = Some(teed(&joined.stdout));
+    context.stderr_tail = Some(teed(&joined.stderr));
+    error
+}
+
+/// Returns the last `lines` lines of `bytes` as text, with `...` in front if any were dropped.
+pub(crate) fn tail(bytes: &[u8], lines: usize) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let text = text.trim_end();
+    match text.rmatch_indices('\n').nth(lines.saturating_sub(1)) {
+        Some((index, _)) if lines > 0 => format!("...\n{}", &text[index + 1..]),
+        _ => text.to_string(),
+    }
+}
+
+pub(crate) fn run_span(command: &Command, options: &Options) -> Span {
+    logging::run_span(
+        &options.redact(command),
+        command.get_program(),
+        command.get_current_dir(),
+    )
+}
+
+/// Spawn the command, and record its pid to the current span.
+///
+/// `piped_stdout` tells whether stdout is piped to this process, where stderr goes too if it is redirected to stdout.
+pub(crate) fn spawn(
+    command: &mut Command,
+    options: &Options,
+    start: Instant,
+    piped_stdout: bool,
+) -> Result<Child> {
+    options
+        .check_valid()
+        .map_err(|e| spawn_error(command, options, e, start))?;
+    prepare(command, options);
+    let merged = redirect(command, options, piped_stdout)
+        .map_err(|e| spawn_error(command, options, e, start))?;
+    let child = command.spawn();
+    if !options.redirects.is_empty() {
+        // Close the ends of the pipes and the files that the command holds, so that the other ends see them closed when the child does.
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+    }
+    let mut child = child.map_err(|e| spawn_error(command, options, e, start))?;
+    if let Some(merged) = merged {
+        child.stdout = Some(merged_stdout(merged));
+    }
+    logging::record_pid(child.id());
+    Ok(child)
+}
+
+#[cfg(unix)]
+fn merged_stdout(reader: std::io::PipeReader) -> std::process::ChildStdout {
+    std::os::fd::OwnedFd::from(reader).into()
+}
+
+#[cfg(windows)]
+fn merged_stdout(reader: std::io::PipeReader) -> std::process::ChildStdout {
+    std::os::windows::io::OwnedHandle::from(reader).into()
+}
+
+pub(crate) fn spawn_error(
+    command: &dyn std::fmt::Debug,
+    options: &Options,
+    e: std::io::Error,
+    start: Instant,
+) -> Error {
+    event!(options.log_levels().failure, command = ?options.redact(command), error = ?e, "Failed to spawn command.");
+    Error::Spawn {
+        source: e,
+        context: Box::new(Context::new(&options.redact(command), start.elapsed())),
+    }
+}
+
+pub(crate) fn wait_error(
+    command: &dyn std::fmt::Debug,
+    options: &Options,
+    e: std::io::Error,
+    start: Instant,
+) -> Error {
+    event!(options.log_levels().failure, command = ?options.redact(command), error = ?e, "Failed to wait for command.");
+    Error::Wait {
+        source: e,
+        context: Box::new(Context::new(&options.redact(command), start.elapsed())),
+    }
+}
+
+/// Write `input` to `stdin` on a new thread, and close it.
+fn write_stream(stdin: std::process::ChildStdin, input: Input) -> Writer {
+    thread::spawn(move || input.write_to(stdin))
+}
+
+/// How much of a stream its reader thread keeps.
+#[derive(Debug, Clone)]
+enum Capture {
+    Nothing,
+    All,
+    /// The last bytes, up to the limit.
+    Last(usize),
+}
+
+impl Capture {
+    fn keep(&self, buffer: &mut Vec<u8>, bytes: &[u8]) {
+        match self {
+            Self::Nothing => {}
+            Self::All => buffer.extend_from_slice(bytes),
+            Self::Last(limit) => {
+                buffer.extend_from_slice(bytes);
+                let excess = buffer.len().saturating_sub(*limit);
+                buffer.drain(..excess);
+            }
+        }
+    }
+}
+
+/// Read `reader` to the end on a new thread, passing each line to `sink`, and return the bytes that `capture` keeps.
+fn read_stream<R>(mut reader: R, sink: LineSink, capture: Capture) -> Reader
+where
+    R: Read + Send + 'static,
+{
+    // Log on the reader thread to the same subscriber as the thread that runs the command.
+    let dispatch = dispatcher::get_default(Dispatch::clone);
+    thread::spawn(move || {
+        dispatcher::with_default(&dispatch, || {
+            let mut buffer = Vec::new();
+            let mut chunk = [0; 8192];
+            let mut pending = Vec::new();
+            loop {
+                let read = match reader.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(read) => read,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+                let bytes = &chunk[..read];
+                sink.echo(bytes);
+                sink.hold(bytes);
+                capture.keep(&mut buffer, bytes);
+                if sink.is_empty() {
+                    continue;
+                }
+                pending.extend_from_slice(bytes);
+                let mut start = 0;
+                while let Some(end) = pending[start..].iter().position(|&b| b == b'\n') {
+                    let end = start + end + 1;
+                    sink.send(&String::from_utf8_lossy(trim_line_ending(
+                        &pending[start..end],
+                    )));
+                    start = end;
+                }
+                pending.drain(..start);
+            }
+            if !pending.is_empty() {
+                sink.send(&String::from_utf8_lossy(trim_line_ending(&pending)));
+            }
+            Ok(buffer)
+        })
+    })
+}
+
+fn trim_line_ending(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+fn join(handle: Option<Reader>) -> std::io::Result<Vec<u8>> {
+    let Some(handle) = handle else {
+        return Ok(Vec::new());
+    };
+    handle
+        .join()
+        .map_err(|_| std::io::Error::other("Reader thread panicked."))?
+}
+
+/// Wait for the child to exit, and terminate it if it runs longer than the timeout.
+pub(crate) fn wait(
+    command: &dyn std::fmt::Debug,
+    child: &mut Child,
+    options: &Options,
+    start: Instant,
+) -> Result<ExitStatus> {
+    let waited = match options.timeout {
+        Some(timeout) => wait_until(child, start + timeout),
+        None => child.wait().map(Some),
+    };
+    match waited {
+        Ok(Some(status)) => Ok(status),
+        Ok(None) => Err(timed_out(command, child, options, start)),
+        Err(e) => Err(wait_error(command, options, e, start)),
+    }
+}
+
+/// Terminate the timed out child and return `Error::Timeout`.
+pub(crate) fn timed_out(
+    command: &dyn std::fmt::Debug,
+    child: &mut Child,
+    options: &Options,
+    start: Instant,
+) -> Error {
+    log_timed_out(command, options);
+    timeout_error(command, options, terminate(child, options), start)
+}
+
+pub(crate) fn log_timed_out(command: &dyn std::fmt::Debug, options: &Options) {
+    event!(options.log_levels().failure, command = ?options.redact(command), grace_period = ?options.grace_period, "Command timed out, terminating it.");
+}
+
+/// Returns the error for a timed out command, given whether the termination had to be forced.
+pub(crate) fn timeout_error(
+    command: &dyn std::fmt::Debug,
+    options: &Options,
+    terminated: std::io::Result<bool>,
+    start: Instant,
+) -> Error {
+    let elapsed = start.elapsed();
+    logging::record_duration(elapsed);
+    match terminated {
+        Ok(forced) => {
+            event!(options.log_levels().failure, command = ?options.redact(command), elapsed = ?elapsed, forced = forced, "Failed to run command within timeout.");
+            Error::Timeout {
+                elapsed,
+                forced,
+                context: Box::new(Context::new(&options.redact(command), elapsed)),
+            }
+        }
+        Err(e) => {
+            event!(options.log_levels().failure, command = ?options.redact(command), error = ?e, "Failed to terminate command.");
+            Error::Wait {
+                source: e,
+                context: Box::new(Context::new(&options.redact(command), elapsed)),
+            }
+        }
+    }
+}
+
+/// Wait for the child to exit until `deadline`, and return `None` if it is still running.
+pub(crate) fn wait_until(
+    child: &mut Child,
+    deadline: Instant,
+) -> std::io::Result<Option<ExitStatus>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL.min(deadline - now));
+    }
+}
+
+/// Ask the child to exit, and kill it if it is still running after the grace period.
+///
+/// If the child was spawned in its own process group, the whole group is signaled.
+/// Returns `true` if the child had to be killed.
+/// On Windows there is no graceful termination, so the child is killed right away.
+fn terminate(child: &mut Child, options: &Options) -> std::io::Result<bool> {
+    #[cfg(unix)]
+    {
+        signal(child.id(), libc::SIGTERM, options.process_group)?;
+        if wait_until(child, Instant::now() + options.grace_period)?.is_some() {
+            return Ok(false);
+        }
+        event!(
+            Level::Debug,
+            pid = child.id(),
+            "Command did not exit within grace period, killing it."
+        );
+    }
+    kill(child, options)?;
+    Ok(true)
+}
+
+/// Kill the child, or its whole process group if it was spawned in one, and reap it.
+pub(crate) fn kill(child: &mut Child, options: &Options) -> std::io::Result<()> {
+    #[cfg(unix)]
+    signal(child.id(), libc::SIGKILL, options.process_group)?;
+    #[cfg(windows)]
+    {
+        let _ = options;
+        child.kill()?;
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// The primitives of an async runtime that async execution is built on.
+///
+/// Everything else about running a command asynchronously is shared by the runtimes, in `run_async` and `output_async`.
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+pub(crate) trait Runtime {
+    type Command: SetStdio + std::fmt::Debug + Send + Sync;
+    type Child: Send;
+    type Stdout: Send;
+    type Stderr: Send;
+
+    fn program(command: &Self::Command) -> &std::ffi::OsStr;
+    fn current_dir(command: &Self::Command) -> Option<&std::path::Path>;
+    fn spawn(command: &mut Self::Command) -> std::io::Result<Self::Child>;
+    /// Returns the pid of the child, if it has not been reaped yet.
+    fn id(child: &Self::Child) -> Option<u32>;
+    fn take_stdout(child: &mut Self::Child) -> Option<Self::Stdout>;
+    fn take_stderr(child: &mut Self::Child) -> Option<Self::Stderr>;
+    fn read_stdout(
+        stdout: Self::Stdout,
+    ) -> impl std::future::Future<Output = std::io::Result<Vec<u8>>> + Send;
+    fn read_stderr(
+        stderr: Self::Stderr,
+    ) -> impl std::future::Future<Output = std::io::Result<Vec<u8>>> + Send;
+    fn wait(
+        child: &mut Self::Child,
+    ) -> impl std::future::Future<Output = std::io::Result<ExitStatus>> + Send;
+    /// Start killing the child, without waiting for it to exit.
+    fn start_kill(child: &mut Self::Child) -> std::io::Result<()>;
+    fn sleep(duration: Duration) -> impl std::future::Future<Output = ()> + Send;
+}
+
+/// Run the command asynchronously to completion, classify its exit status, and return the accepted exit code.
+///
+/// If the future is dropped before it completes, the process group of the command is killed, if it was spawned in one.
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+pub(crate) async fn run_async<R: Runtime>(
+    command: &mut R::Command,
+    options: &Options,
+) -> Result<i32> {
+    let span = run_span_async::<R>(command, options);
+    async {
+        event!(options.log_levels().start, command = ?options.redact(command), "Running command.");
+        let start = Instant::now();
+        let (mut child, streams) =
+            spawn_async::<R>(command, options, start, options.pipes_stdout(), false)?;
+        let guard = GroupGuard::new(R::id(&child), options);
+        let status = wait_async::<R>(command, &mut child, options, start).await;
+        guard.disarm();
+        let status = match status {
+            Ok(status) => status,
+            Err(e) => return Err(streams.report::<R>(e, options).await),
+        };
+        let joined = streams
+            .join()
+            .await
+            .map_err(|e| wait_error(command, options, e, start))?;
+        check_status(command, status, start.elapsed(), options)
+            .map_err(|e| report_output(e, options, &joined))
+    }
+    .instrument(span)
+    .await
+}
+
+/// Run the command asynchronously to completion with piped stdout and stderr, and classify its exit status.
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+pub(crate) async fn output_async<R: Runtime>(
+    command: &mut R::Command,
+    options: &Options,
+) -> Result<Output> {
+    let span = run_span_async::<R>(command, options);
+    async {
+        event!(options.log_levels().start, command = ?options.redact(command), "Running command.");
+        command.set_stdout(Stdio::piped());
+        command.set_stderr(Stdio::piped());
+        let start = Instant::now();
+        let (mut child, mut streams) = spawn_async::<R>(command, options, start, true, true)?;
+        let guard = GroupGuard::new(R::id(&child), options);
+        let merged = streams.merged.take();
+        let stdout = R::take_stdout(&mut child);
+        let stderr = R::take_stderr(&mut child);
+        let waited = try_join(
+            wait_async::<R>(command, &mut child, options, start),
+            try_join(
+                async {
+                    match (merged, stdout) {
+                        (Some(merged), _) => Ok(on_thread(move || read_all(merged)).await?),
+                        (None, Some(stdout)) => Ok(R::read_stdout(stdout).await?),
+                        (None, None) => Ok(Vec::new()),
+                    }
+                },
+                async {
+                    match stderr {
+                        Some(stderr) => Ok(R::read_stderr(stderr).await?),
+                        None => Ok(Vec::new()),
+                    }
+                },
+            ),
+        )
+        .await;
+        guard.disarm();
+        let (status, (stdout, stderr)) = match waited {
+            Ok(waited) => waited,
+            Err(e) => return Err(streams.report::<R>(e, options).await),
+        };
+        let mut joined = streams
+            .join()
+            .await
+            .map_err(|e| wait_error(command, options, e, start))?;
+        // The streams that are not read for their lines are read by the runtime, so only one of each pair is not empty.
+        joined.stdout.extend(stdout);
+        joined.stderr.extend(stderr);
+        finish_output(command, options, status, joined, start.elapsed())
+    }
+    .instrument(span)
+    .await
+}
+
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+fn run_span_async<R: Runtime>(command: &R::Command, options: &Options) -> Span {
+    logging::run_span(
+        &options.redact(command),
+        R::program(command),
+        R::current_dir(command),
+    )
+}
+
+/// Spawn the command on the runtime `R` with its redirections applied, start writing its input and reading the lines of its streams, and record its pid to the current span.
+///
+/// The streams whose lines are passed to a callback, logged, teed or held back are piped to threads, as they are by `Streams::start`, and the bytes read are kept if `capture` is `true`.
+/// If stderr is redirected to a stdout that is piped but not read for its lines, the read end of the pipe they both go to is returned, as `spawn` does.
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+fn spawn_async<R: Runtime>(
+    command: &mut R::Command,
+    options: &Options,
+    start: Instant,
+    piped_stdout: bool,
+    capture: bool,
+) -> Result<(R::Child, ThreadStreams)> {
+    options
+        .check_valid()
+        .map_err(|e| spawn_error(command, options, e, start))?;
+    let pipe = |piped: bool| piped.then(std::io::pipe).transpose();
+    let (stdout, stderr) = pipe(options.pipes_stdout())
+        .and_then(|stdout| Ok((stdout, pipe(options.pipes_stderr())?)))
+        .map_err(|e| spawn_error(command, options, e, start))?;
+    let stdout = stdout.map(|(reader, writer)| {
+        command.set_stdout(writer.into());
+        reader
+    });
+    let stderr = stderr.map(|(reader, writer)| {
+        command.set_stderr(writer.into());
+        reader
+    });
+    let merged = redirect(command, options, piped_stdout)
+        .map_err(|e| spawn_error(command, options, e, start))?;
+    let input = match options.stdin_input() {
+        Some(input) => {
+            let (reader, writer) =
+                std::io::pipe().map_err(|e| spawn_error(command, options, e, start))?;
+            command.set_stdin(reader.into());
+            Some((input.clone(), writer))
+        }
+        None => None,
+    };
+    let child = R::spawn(command);
+    if !options.redirects.is_empty() || stdout.is_some() || stderr.is_some() {
+        // Close the ends of the pipes and the files that the command holds, so that the other ends see them closed when the child does.
+        command.set_stdin(Stdio::null());
+        command.set_stdout(Stdio::null());
+        command.set_stderr(Stdio::null());
+    }
+    let child = child.map_err(|e| spawn_error(command, options, e, start))?;
+    if let Some(pid) = R::id(&child) {
+        logging::record_pid(pid);
+    }
+    let stdin = input.map(|(input, writer)| {
+        Box::pin(on_thread(move || input.write_to(writer))) as std::pin::Pin<Box<_>>
+    });
+    // If stderr is redirected to stdout, both are read from the pipe they go to, and the pipe of stdout is closed.
+    let (stdout, merged) = match stdout {
+        Some(stdout) => (Some(merged.unwrap_or(stdout)), None),
+        None => (None, merged),
+    };
+    let lines = Streams::read(stdout, stderr, options, capture);
+    Ok((
+        child,
+        ThreadStreams {
+            merged,
+            stdin,
+            lines,
+        },
+    ))
+}
+
+/// The streams of a child spawned on an async runtime that are read or written from threads.
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+struct ThreadStreams {
+    /// The read end of the pipe that stdout and stderr both go to, if stderr is redirected to a piped stdout.
+    merged: Option<std::io::PipeReader>,
+    /// The thread that writes the input of the child.
+    stdin: Option<std::pin::Pin<Box<dyn std::future::Future<Output = std::io::Result<()>> + Send>>>,
+    /// The threads that read the streams whose lines are passed to a callback, logged, teed or held back.
+    lines: Streams,
+}
+
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+impl ThreadStreams {
+    /// Wait for the input of the child to be written and the streams read for their lines to be closed, and return what was kept of them.
+    ///
+    /// The command may exit without reading all of its input, so a broken pipe is not an error, as it is not in `Streams::join`.
+    async fn join(self) -> std::io::Result<Joined> {
+        if let Some(stdin) = self.stdin {
+            match stdin.await {
+                Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => return Err(e),
+                _ => {}
+            }
+        }
+        let lines = self.lines;
+        on_thread(move || lines.join()).await
+    }
+
+    /// Wait for the streams read for their lines during the grace period at most, and report their output with `error`, as `Streams::report` does.
+    async fn report<R: Runtime>(self, error: Error, options: &Options) -> Error {
+        let deadline = Instant::now() + options.grace_period;
+        while !self.lines.is_finished() {
+            if Instant::now() >= deadline {
+                event!(
+                    Level::Debug,
+                    "Streams of command were not closed within grace period, leaving them."
+                );
+                return error;
+            }
+            R::sleep(POLL_INTERVAL).await;
+        }
+        self.lines.report(error, options)
+    }
+}
+
+/// Run `f` on a new thread, and return a future of its result that any runtime can await.
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+fn on_thread<T: Send + 'static>(
+    f: impl FnOnce() -> std::io::Result<T> + Send + 'static,
+) -> impl std::future::Future<Output = std::io::Result<T>> + Send {
+    type State<T> = (Option<std::io::Result<T>>, Option<std::task::Waker>);
+
+    let state = Arc::new(Mutex::new(State::<T>::default()));
+    let thread_state = Arc::clone(&state);
+    thread::spawn(move || {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+            .unwrap_or_else(|_| Err(std::io::Error::other("Thread panicked.")));
+        let mut state = thread_state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.0 = Some(result);
+        if let Some(waker) = state.1.take() {
+            waker.wake();
+        }
+    });
+    std::future::poll_fn(move |cx| {
+        let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(result) = state.0.take() {
+            return std::task::Poll::Ready(result);
+        }
+        state.1 = Some(cx.waker().clone());
+        std::task::Poll::Pending
+    })
+}
+
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+fn read_all(mut reader: impl Read) -> std::io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    reader.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Wait asynchronously for the child to exit, and terminate it if it runs longer than the timeout.
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+async fn wait_async<R: Runtime>(
+    command: &R::Command,
+    child: &mut R::Child,
+    options: &Options,
+    start: Instant,
+) -> Result<ExitStatus> {
+    let waited = match options.timeout {
+        Some(timeout) => {
+            let remaining = timeout.saturating_sub(start.elapsed());
+            race(async { Some(R::wait(child).await) }, async {
+                R::sleep(remaining).await;
+                None
+            })
+            .await
+        }
+        None => Some(R::wait(child).await),
+    };
+    match waited {
+        Some(Ok(status)) => Ok(status),
+        Some(Err(e)) => Err(wait_error(command, options, e, start)),
+        None => {
+            log_timed_out(command, options);
+            let terminated = terminate_async::<R>(child, options).await;
+            Err(timeout_error(command, options, terminated, start))
+        }
+    }
+}
+
+/// Ask the child to exit, and kill it if it is still running after the grace period, as `terminate` does.
+///
+/// Returns `true` if the child had to be killed.
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+async fn terminate_async<R: Runtime>(
+    child: &mut R::Child,
+    options: &Options,
+) -> std::io::Result<bool> {
+    #[cfg(unix)]
+    if let Some(pid) = R::id(child) {
+        signal(pid, libc::SIGTERM, options.process_group)?;
+        let exited = race(async { R::wait(child).await.is_ok() }, async {
+            R::sleep(options.grace_period).await;
+            false
+        })
+        .await;
+        if exited {
+            return Ok(false);
+        }
+        if options.process_group {
+            signal(pid, libc::SIGKILL, true)?;
+        }
+    }
+    #[cfg(windows)]
+    let _ = options;
+    R::start_kill(child)?;
+    R::wait(child).await?;
+    Ok(true)
+}
+
+/// Poll both futures, and return the output of the first one that completes.
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+async fn race<T>(
+    a: impl std::future::Future<Output = T>,
+    b: impl std::future::Future<Output = T>,
+) -> T {
+    let (mut a, mut b) = (std::pin::pin!(a), std::pin::pin!(b));
+    std::future::poll_fn(|cx| match a.as_mut().poll(cx) {
+        std::task::Poll::Ready(value) => std::task::Poll::Ready(value),
+        std::task::Poll::Pending => b.as_mut().poll(cx),
+    })
+    .await
+}
+
+/// Poll both futures until both succeed or one fails, and return both outputs or the first error.
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+async fn try_join<A, B>(
+    a: impl std::future::Future<Output = Result<A>>,
+    b: impl std::future::Future<Output = Result<B>>,
+) -> Result<(A, B)> {
+    use std::task::Poll;
+
+    let (mut a, mut b) = (std::pin::pin!(a), std::pin::pin!(b));
+    let (mut a_output, mut b_output) = (None, None);
+    std::future::poll_fn(|cx| {
+        if a_output.is_none() {
+            if let Poll::Ready(output) = a.as_mut().poll(cx) {
+                a_output = Some(output?);
+            }
+        }
+        if b_output.is_none() {
+            if let Poll::Ready(output) = b.as_mut().poll(cx) {
+                b_output = Some(output?);
+            }
+        }
+        match (a_output.take(), b_output.take()) {
+            (Some(a), Some(b)) => Poll::Ready(Ok((a, b))),
+            (a, b) => {
+                (a_output, b_output) = (a, b);
+                Poll::Pending
+            }
+        }
+    })
+    .await
+}
+
+/// Kills the process group of a command that is still running when the future that runs it is dropped.
+///
+/// The async runtimes only kill the shell itself on drop, which would leave the rest of its group running.
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+struct GroupGuard(Option<u32>);
+
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+impl GroupGuard {
+    fn new(pid: Option<u32>, options: &Options) -> Self {
+        Self(pid.filter(|_| options.process_group))
+    }
+
+    /// Leave the group as it is, because the command exited or was terminated.
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-process"))]
+impl Drop for GroupGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.0 {
+            event!(
+                Level::Debug,
+                pid = pid,
+                "Killing process group of dropped command."
+            );
+            if let Err(e) = kill_group(pid) {
+                event!(Level::Debug, pid = pid, error = ?e, "Failed to kill process group of dropped command.");
+            }
+        }
+    }
+}
+
+/// Send `signal` to the process `pid`, or to its whole process group if `process_group` is `true`.
+#[cfg(unix)]
+pub(crate) fn signal(pid: u32, signal: i32, process_group: bool) -> std::io::Result<()> {
+    let pid = libc::pid_t::try_from(pid).map_err(std::io::Error::other)?;
+    send_signal(if process_group { -pid } else { pid }, signal)
+}
+
+/// Kill the processes that are left in the process group of `pid`, if there are any.
+#[cfg(unix)]
+pub(crate) fn kill_group(pid: u32) -> std::io::Result<()> {
+    match signal(pid, libc::SIGKILL, true) {
+        Err(e) if e.raw_os_error() == Some(libc::ESRCH) => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(unix)]
+fn send_signal(pid: libc::pid_t, signal: i32) -> std::io::Result<()> {
+    // SAFETY: `kill` has no memory safety requirements.
+    if unsafe { libc::kill(pid, signal) } == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn get_signal(a: ExitStatus) -> Option<Signal> {
+    use std::os::unix::process::ExitStatusExt;
+    a.signal()
+        .map(|signal| Signal::new(signal, a.core_dumped()))
+}
+
+#[cfg(windows)]
+fn get_signal(_: ExitStatus) -> Option<Signal> {
+    None
+}
+
+/// Classify the exit status of a finished command against the accepted exit codes, and return the exit code.
+pub(crate) fn check_status(
+    command: &dyn std::fmt::Debug,
+    status: ExitStatus,
+    duration: Duration,
+    options: &Options,
+) -> Result<i32> {
+    classify(command, options, status, duration, |code| {
+        options.accept(code)
+    })
+}
+
+/// Classify the exit status of a finished command, log the result, and record it to the current span.
+///
+/// The exit code is mapped with `map`, and the command failed if it returns `None`.
+fn classify<T>(
+    command: &dyn std::fmt::Debug,
+    options: &Options,
+    status: ExitStatus,
+    duration: Duration,
+    map: impl FnOnce(i32) -> Option<T>,
+) -> Result<T> {
+    let levels = options.log_levels();
+    logging::record_duration(duration);
+    if let Some(exit_code) = status.code() {
+        logging::record_exit_code(exit_code);
+        match map(exit_code) {
+            Some(value) if exit_code == 0 => {
+                event!(levels.success, command = ?options.redact(command), "Succeeded to run command with zero exit code.");
+                Ok(value)
+            }
+            Some(value) => {
+                event!(levels.success, command = ?options.redact(command), exit_code = ?exit_code, "Succeeded to run command with accepted exit code.");
+                Ok(value)
+            }
+            None => {
+                event!(levels.failure, command = ?options.redact(command), exit_code = ?exit_code, "Failed to run command with exit code not accepted.");
+                Err(Error::ExitCode {
+                    code: exit_code,
+                    context: Box::new(Context::new(&options.redact(command), duration)),
+                })
+            }
+        }
+    } else if let Some(signal) = get_signal(status) {
+        logging::record_signal(signal);
+        event!(levels.failure, command = ?options.redact(command), signal = %signal, "Failed to run command with signal.");
+        Err(Error::Signal {
+            signal,
+            context: Box::new(Context::new(&options.redact(command), duration)),
+        })
+    } else {
+        event!(levels.failure, command = ?options.redact(command), "Failed to run command with no exit code and signal.");
+        Err(Error::NoExitCodeAndSignal {
+            context: Box::new(Context::new(&options.redact(command), duration)),
+        })
+    }
+}
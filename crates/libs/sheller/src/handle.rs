@@ -0,0 +1,278 @@
+use crate::{
+    exec,
+    logging::{event, Level},
+    Error, Result,
+};
+use std::{
+    process::{Child, ExitStatus},
+    time::Instant,
+};
+use tracing::Span;
+
+/// What a `Handle` does with a command that is still running when the handle is dropped.
+///
+/// # Process groups
+///
+/// A command spawned with `Sheller::process_group` runs in its own process group, which is detached from the terminal.
+/// `ctrl + c` in the terminal sends `SIGINT` only to this process, and sheller does not forward it to the group.
+/// `Kill` signals the whole group on drop, including the processes that the command left running after it exited.
+/// But nothing is signaled if this process exits without dropping the handle, such as by `std::process::exit` or by a `SIGINT` or `SIGTERM` that it does not handle, and then the group keeps running.
+/// If the commands must not outlive this process, handle these signals and drop the handles, or forward the signal with `Handle::signal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DropPolicy {
+    /// Kill the command, or its whole process group if it was spawned in one, and reap it.
+    ///
+    /// The process group is killed even if the command itself has already exited.
+    #[default]
+    Kill,
+    /// Leave the command running.
+    Detach,
+    /// Block until the command exits.
+    Wait,
+}
+
+/// A handle to a command running in the background.
+///
+/// Returned by `Sheller::spawn` and `CommandExt::spawn_managed`.
+/// The final result goes through the same exit code and signal classification as `try_run`.
+/// Please see `DropPolicy` for what happens when the handle is dropped before the command exits.
+#[derive(Debug)]
+pub struct Handle {
+    child: Child,
+    command: exec::Rendered,
+    options: exec::Options,
+    start: Instant,
+    streams: Option<exec::Streams>,
+    /// The span of the run, entered whenever the handle does something with the command.
+    span: Span,
+    drop_policy: DropPolicy,
+    finished: bool,
+}
+
+impl Handle {
+    pub(crate) fn spawn(
+        command: &mut std::process::Command,
+        options: exec::Options,
+    ) -> Result<Self> {
+        let span = exec::run_span(command, &options);
+        let start = Instant::now();
+        let (child, streams) = span.in_scope(|| -> Result<_> {
+            event!(options.log_levels().start, command = ?options.redact(command), "Spawning command.");
+            options.pipe_line_streams(command);
+            let mut child = exec::spawn(command, &options, start, options.pipes_stdout())?;
+            let streams = exec::Streams::start(&mut child, &options, false);
+            Ok((child, streams))
+        })?;
+        Ok(Self {
+            child,
+            command: exec::Rendered::new(command, &options),
+            options,
+            start,
+            streams: Some(streams),
+            span,
+            drop_policy: DropPolicy::default(),
+            finished: false,
+        })
+    }
+
+    /// Returns the OS-assigned process identifier of the command.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let mut handle = Sheller::new("echo hello").spawn().unwrap();
+    /// assert!(handle.pid() > 0);
+    /// handle.wait().unwrap();
+    /// ```
+    #[must_use]
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Set what happens to the command if the handle is dropped while it is still running.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::{DropPolicy, Sheller};
+    ///
+    /// let mut handle = Sheller::new("echo hello").spawn().unwrap();
+    /// handle.set_drop_policy(DropPolicy::Wait);
+    /// ```
+    pub fn set_drop_policy(&mut self, drop_policy: DropPolicy) {
+        self.drop_policy = drop_policy;
+    }
+
+    /// Wait for the command to exit and return its exit status.
+    ///
+    /// If a timeout was set, the command is terminated when it runs out.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let mut handle = Sheller::new("echo hello").spawn().unwrap();
+    /// assert!(handle.wait().unwrap().success());
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run.
+    pub fn wait(&mut self) -> Result<ExitStatus> {
+        let _span = self.span.clone().entered();
+        let status = exec::wait(&self.command, &mut self.child, &self.options, self.start);
+        self.finished = true;
+        let status = status.map_err(|e| self.report(e))?;
+        self.classify(status)
+    }
+
+    /// Return the exit status if the command has exited, or `None` if it is still running.
+    ///
+    /// If a timeout was set and it has run out, the command is terminated.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let mut handle = Sheller::new("echo hello").spawn().unwrap();
+    /// while handle.try_wait().unwrap().is_none() {
+    ///     std::thread::sleep(std::time::Duration::from_millis(10));
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run.
+    pub fn try_wait(&mut self) -> Result<Option<ExitStatus>> {
+        let _span = self.span.clone().entered();
+        let deadline = self
+            .options
+            .timeout
+            .map_or(self.start, |timeout| self.start + timeout);
+        let status = self
+            .child
+            .try_wait()
+            .map_err(|e| exec::wait_error(&self.command, &self.options, e, self.start))?;
+        match status {
+            Some(status) => {
+                self.finished = true;
+                self.classify(status).map(Some)
+            }
+            None if self.options.timeout.is_some() && Instant::now() >= deadline => {
+                self.finished = true;
+                let e = exec::timed_out(&self.command, &mut self.child, &self.options, self.start);
+                Err(self.report(e))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Kill the command, or its whole process group if it was spawned in one, and wait for it to exit.
+    ///
+    /// If the command has already exited, the processes it left running in its process group are still killed, as they are on drop.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// # #[cfg(unix)]
+    /// # {
+    /// let mut handle = Sheller::new("sleep 10").spawn().unwrap();
+    /// handle.kill().unwrap();
+    /// assert!(handle.wait().is_err());
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command could not be killed.
+    pub fn kill(&mut self) -> Result<()> {
+        let _span = self.span.clone().entered();
+        if self.child.try_wait()?.is_some() {
+            #[cfg(unix)]
+            if self.options.process_group {
+                event!(Level::Info, command = ?self.options.redact(&self.command), pid = self.pid(), "Killing process group of exited command.");
+                exec::kill_group(self.child.id()).map_err(|e| {
+                    event!(self.options.log_levels().failure, command = ?self.options.redact(&self.command), error = ?e, "Failed to kill process group.");
+                    e
+                })?;
+            }
+            return Ok(());
+        }
+        event!(Level::Info, command = ?self.options.redact(&self.command), pid = self.pid(), "Killing command.");
+        exec::kill(&mut self.child, &self.options).map_err(|e| {
+            event!(self.options.log_levels().failure, command = ?self.options.redact(&self.command), error = ?e, "Failed to kill command.");
+            e
+        })?;
+        Ok(())
+    }
+
+    /// Send `signal` to the command, or to its whole process group if it was spawned in one.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let mut handle = Sheller::new("sleep 10").spawn().unwrap();
+    /// handle.signal(libc::SIGTERM).unwrap();
+    /// assert!(handle.wait().is_err());
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the signal could not be sent.
+    #[cfg(unix)]
+    pub fn signal(&self, signal: i32) -> Result<()> {
+        event!(Level::Debug, command = ?self.options.redact(&self.command), pid = self.pid(), signal = signal, "Sending signal to command.");
+        exec::signal(self.child.id(), signal, self.options.process_group)?;
+        Ok(())
+    }
+
+    /// Report the output of the command with `error`, if it failed before its streams were joined.
+    fn report(&mut self, error: Error) -> Error {
+        match self.streams.take() {
+            Some(streams) => streams.report(error, &self.options),
+            None => error,
+        }
+    }
+
+    fn classify(&mut self, status: ExitStatus) -> Result<ExitStatus> {
+        let joined = match self.streams.take() {
+            Some(streams) => streams
+                .join()
+                .map_err(|e| exec::wait_error(&self.command, &self.options, e, self.start))?,
+            None => exec::Joined::default(),
+        };
+        exec::check_status(&self.command, status, self.start.elapsed(), &self.options)
+            .map_err(|e| exec::report_output(e, &self.options, &joined))?;
+        Ok(status)
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        if self.finished || matches!(self.child.try_wait(), Ok(Some(_))) {
+            #[cfg(unix)]
+            if self.options.process_group && self.drop_policy == DropPolicy::Kill {
+                // The command may have left processes running in its group.
+                if let Err(e) = exec::kill_group(self.child.id()) {
+                    event!(self.options.log_levels().failure, command = ?self.options.redact(&self.command), error = ?e, "Failed to kill process group on drop.");
+                }
+            }
+            return;
+        }
+        match self.drop_policy {
+            DropPolicy::Kill => {
+                event!(Level::Debug, command = ?self.options.redact(&self.command), pid = self.pid(), "Killing command on drop.");
+                if let Err(e) = exec::kill(&mut self.child, &self.options) {
+                    event!(self.options.log_levels().failure, command = ?self.options.redact(&self.command), error = ?e, "Failed to kill command on drop.");
+                }
+            }
+            DropPolicy::Detach => {
+                event!(Level::Debug, command = ?self.options.redact(&self.command), pid = self.pid(), "Detaching command on drop.");
+            }
+            DropPolicy::Wait => {
+                event!(Level::Debug, command = ?self.options.redact(&self.command), pid = self.pid(), "Waiting for command on drop.");
+                if let Err(e) = self.child.wait() {
+                    event!(self.options.log_levels().failure, command = ?self.options.redact(&self.command), error = ?e, "Failed to wait for command on drop.");
+                }
+            }
+        }
+    }
+}
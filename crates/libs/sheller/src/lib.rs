@@ -1,27 +1,227 @@
-use tracing::{debug, error, info};
+use logging::event;
 
+#[cfg(feature = "async-process")]
+pub mod async_process;
+mod exec;
+mod handle;
+mod logging;
 mod macros;
+mod pipeline;
+mod shell;
+mod signal;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+#[cfg(feature = "tokio")]
+pub use self::tokio::AsyncCommandExt;
+pub use handle::{DropPolicy, Handle};
+pub use logging::{log_levels, set_log_levels, Level, LogLevels};
+#[doc(hidden)]
+pub use macros::__sh_names_are_passed;
+pub use pipeline::Pipeline;
+pub use shell::{Shell, ShellPolicy};
+pub use signal::Signal;
+
+/// The phase of running a command in which an `Error` occurred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// The command could not be started.
+    Spawn,
+    /// The command was started, but waiting for it or reading its output failed, or it timed out.
+    Wait,
+    /// The command ran to completion and exited unsuccessfully.
+    Exit,
+}
+
+/// What is known about the command an `Error` came from.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Context {
+    /// The rendered command line.
+    pub command: String,
+    /// The time elapsed from spawning the command until the error occurred.
+    pub duration: std::time::Duration,
+    /// The last lines of stderr, if the output of the command was captured.
+    ///
+    /// If the output was teed, it is everything that was kept of stderr.
+    pub stderr_tail: Option<String>,
+    /// Everything that was kept of stdout, if the output of the command was teed.
+    pub stdout_tail: Option<String>,
+}
+
+impl Context {
+    pub(crate) fn new(command: &dyn std::fmt::Debug, duration: std::time::Duration) -> Self {
+        Self {
+            command: format!("{command:?}"),
+            duration,
+            stderr_tail: None,
+            stdout_tail: None,
+        }
+    }
+}
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     Io(std::io::Error),
-    ExitCode(i32),
-    Signal(i32),
-    NoExitCodeAndSignal,
+    Utf8(std::string::FromUtf8Error),
+    /// The command could not be spawned.
+    Spawn {
+        source: std::io::Error,
+        context: Box<Context>,
+    },
+    /// Waiting for the command or reading its output failed.
+    Wait {
+        source: std::io::Error,
+        context: Box<Context>,
+    },
+    /// The command exited with an exit code that was not accepted.
+    ExitCode {
+        code: i32,
+        context: Box<Context>,
+    },
+    /// The command was terminated by a signal.
+    Signal {
+        signal: Signal,
+        context: Box<Context>,
+    },
+    /// The command exited with neither an exit code nor a signal.
+    NoExitCodeAndSignal {
+        context: Box<Context>,
+    },
+    /// The command did not exit within the timeout and was terminated.
+    Timeout {
+        /// The time elapsed from spawning the command until it was terminated.
+        elapsed: std::time::Duration,
+        /// `true` if the command ignored the graceful termination and had to be killed.
+        forced: bool,
+        context: Box<Context>,
+    },
+}
+
+impl Error {
+    /// Returns the phase in which the error occurred, or `None` if it did not come from running a command.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::{Phase, Sheller};
+    ///
+    /// let error = Sheller::new("exit 1").try_run().unwrap_err();
+    /// assert_eq!(error.phase(), Some(Phase::Exit));
+    /// ```
+    #[must_use]
+    pub fn phase(&self) -> Option<Phase> {
+        match self {
+            Error::Io(_) | Error::Utf8(_) => None,
+            Error::Spawn { .. } => Some(Phase::Spawn),
+            Error::Wait { .. } | Error::Timeout { .. } => Some(Phase::Wait),
+            Error::ExitCode { .. } | Error::Signal { .. } | Error::NoExitCodeAndSignal { .. } => {
+                Some(Phase::Exit)
+            }
+        }
+    }
+
+    /// Returns the context of the command the error came from, or `None` if it did not come from running a command.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let error = Sheller::new("echo oops >&2; exit 1").try_output().unwrap_err();
+    /// let context = error.context().unwrap();
+    /// assert_eq!(context.stderr_tail.as_deref(), Some("oops"));
+    /// ```
+    #[must_use]
+    pub fn context(&self) -> Option<&Context> {
+        match self {
+            Error::Io(_) | Error::Utf8(_) => None,
+            Error::Spawn { context, .. }
+            | Error::Wait { context, .. }
+            | Error::ExitCode { context, .. }
+            | Error::Signal { context, .. }
+            | Error::NoExitCodeAndSignal { context }
+            | Error::Timeout { context, .. } => Some(context),
+        }
+    }
+
+    pub(crate) fn context_mut(&mut self) -> Option<&mut Context> {
+        match self {
+            Error::Io(_) | Error::Utf8(_) => None,
+            Error::Spawn { context, .. }
+            | Error::Wait { context, .. }
+            | Error::ExitCode { context, .. }
+            | Error::Signal { context, .. }
+            | Error::NoExitCodeAndSignal { context }
+            | Error::Timeout { context, .. } => Some(context),
+        }
+    }
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Error::Io(e) => write!(f, "I/O error: {e}"),
-            Error::ExitCode(exit_code) => write!(f, "Exit code: {exit_code}"),
-            Error::Signal(signal) => write!(f, "Signal: {signal}"),
-            Error::NoExitCodeAndSignal => write!(f, "No exit code and signal"),
+            Error::Io(e) => return write!(f, "I/O error: {e}"),
+            Error::Utf8(e) => return write!(f, "UTF-8 error: {e}"),
+            Error::Spawn { source, context } => {
+                write!(f, "Failed to spawn `{}`: {source}", context.command)?;
+            }
+            Error::Wait { source, context } => write!(
+                f,
+                "Failed to wait for `{}` after {:?}: {source}",
+                context.command, context.duration
+            )?,
+            Error::ExitCode { code, context } => write!(
+                f,
+                "`{}` exited with code {code} after {:?}",
+                context.command, context.duration
+            )?,
+            Error::Signal { signal, context } => write!(
+                f,
+                "`{}` was terminated by {signal} after {:?}",
+                context.command, context.duration
+            )?,
+            Error::NoExitCodeAndSignal { context } => write!(
+                f,
+                "`{}` exited with no exit code and signal after {:?}",
+                context.command, context.duration
+            )?,
+            Error::Timeout {
+                elapsed,
+                forced,
+                context,
+            } => {
+                write!(f, "`{}` timed out after {elapsed:?}", context.command)?;
+                if *forced {
+                    write!(f, " and was killed after grace period")?;
+                }
+            }
         }
+        let tail = |text: &str| exec::tail(text.as_bytes(), exec::STDERR_TAIL_LINES);
+        if let Some(stdout_tail) = self.context().and_then(|c| c.stdout_tail.as_ref()) {
+            if !stdout_tail.is_empty() {
+                write!(f, "\nstdout:\n{}", tail(stdout_tail))?;
+            }
+        }
+        if let Some(stderr_tail) = self.context().and_then(|c| c.stderr_tail.as_ref()) {
+            if !stderr_tail.is_empty() {
+                write!(f, "\nstderr:\n{}", tail(stderr_tail))?;
+            }
+        }
+        Ok(())
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) | Error::Spawn { source: e, .. } | Error::Wait { source: e, .. } => {
+                Some(e)
+            }
+            Error::Utf8(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 impl From<std::io::Error> for Error {
     fn from(e: std::io::Error) -> Self {
@@ -29,57 +229,163 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl From<std::string::FromUtf8Error> for Error {
+    fn from(e: std::string::FromUtf8Error) -> Self {
+        Error::Utf8(e)
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Captured output of a command that finished successfully.
+///
+/// Returned by `Sheller::try_output` and `CommandExt::try_output`.
+#[derive(Debug, Clone)]
+pub struct Output {
+    /// The bytes the command wrote to stdout.
+    pub stdout: Vec<u8>,
+    /// The bytes the command wrote to stderr.
+    pub stderr: Vec<u8>,
+    /// The exit status of the command.
+    pub status: std::process::ExitStatus,
+    /// The time elapsed from spawning the command until it exited.
+    pub duration: std::time::Duration,
+}
+
+impl Output {
+    /// Returns stdout decoded as UTF-8 without the trailing newlines, as command substitution in a shell does.
+    ///
+    /// Leading whitespace is kept.
+    /// `\r\n` line endings are normalized to `\n`, so the result is the same on every platform.
+    ///
+    /// # Errors
+    /// Returns an `Err` if stdout is not valid UTF-8.
+    pub fn stdout_string(&self) -> Result<String> {
+        let stdout = String::from_utf8(self.stdout.clone())?;
+        Ok(stdout.trim_end_matches(['\n', '\r']).replace("\r\n", "\n"))
+    }
+}
+
 #[derive(Debug)]
 struct Metadata<'a> {
     env_key: &'a str,
     program: &'a str,
-    args: &'a [&'a str],
+    shell: Shell,
 }
 
 #[cfg(windows)]
 static DEFAULT_METADATA: Metadata = Metadata {
     env_key: "COMSPEC",
     program: "cmd.exe",
-    args: &["/D", "/S", "/C"],
+    shell: Shell::Cmd,
 };
 
 #[cfg(unix)]
 static DEFAULT_METADATA: Metadata = Metadata {
     env_key: "SHELL",
     program: "/bin/sh",
-    args: &["-c"],
+    shell: Shell::Sh,
 };
 
-fn parse_program() -> String {
-    std::env::var(DEFAULT_METADATA.env_key).unwrap_or_else(|e| {
-        debug!(
-            default_program = DEFAULT_METADATA.program,
-            env_key = DEFAULT_METADATA.env_key,
-            error = ?e,
-            "Failed to get shell environment variable, falling back to default program."
-        );
-        DEFAULT_METADATA.program.to_string()
-    })
+fn parse_program() -> Option<String> {
+    std::env::var(DEFAULT_METADATA.env_key)
+        .map_err(|e| {
+            event!(
+                Level::Debug,
+                default_program = DEFAULT_METADATA.program,
+                env_key = DEFAULT_METADATA.env_key,
+                error = ?e,
+                "Failed to get shell environment variable, falling back to default program."
+            );
+        })
+        .ok()
+}
+
+fn to_strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(ToString::to_string).collect()
+}
+
+/// Returns `true` if scripts written for the default shell family also run on `shell`.
+fn is_default_compatible(shell: Shell) -> bool {
+    shell == DEFAULT_METADATA.shell || (shell.is_posix() && DEFAULT_METADATA.shell.is_posix())
+}
+
+/// Pass `script` to the shell program as its last argument.
+///
+/// `cmd.exe` does not understand the `\"` that `std` escapes `"` with on Windows, so with `/S` the script is passed as it is, in the quotes that `/S` strips.
+fn push_script(command: &mut std::process::Command, invocation: &Invocation, script: &str) {
+    #[cfg(windows)]
+    if invocation.shell == Shell::Cmd
+        && invocation
+            .args
+            .iter()
+            .any(|arg| arg.eq_ignore_ascii_case("/S"))
+    {
+        std::os::windows::process::CommandExt::raw_arg(command, format!("\"{script}\""));
+        return;
+    }
+    #[cfg(unix)]
+    let _ = invocation;
+    command.arg(script);
+}
+
+/// The program, flags and shell family that a `Sheller` resolves to.
+#[derive(Debug)]
+struct Invocation {
+    program: String,
+    args: Vec<String>,
+    shell: Shell,
+    /// The program from the environment that was rejected by the `ShellPolicy`.
+    rejected: Option<String>,
 }
 
 /// Sheller is a builder for `std::process::Command` that sets the shell program and arguments.
 ///
 /// Please see the `Sheller::new` method for more information.
-#[derive(Debug)]
 pub struct Sheller {
-    program: String,
-    args: Vec<&'static str>,
+    env_program: Option<String>,
+    policy: ShellPolicy,
+    program: Option<String>,
+    args: Option<Vec<String>>,
+    shell: Option<Shell>,
     script: String,
+    positional: Vec<std::ffi::OsString>,
+    strict: bool,
+    envs: Vec<(std::ffi::OsString, String)>,
+    options: exec::Options,
+}
+
+impl std::fmt::Debug for Sheller {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let secrets = &self.options.secrets;
+        f.debug_struct("Sheller")
+            .field("env_program", &self.env_program)
+            .field("policy", &self.policy)
+            .field("program", &self.program)
+            .field("args", &self.args)
+            .field("shell", &self.shell)
+            .field("script", &secrets.redact(&self.script))
+            .field("positional", &secrets.redact_debug(&self.positional))
+            .field("strict", &self.strict)
+            .field("envs", &secrets.redact_debug(&self.envs))
+            .field("options", &self.options)
+            .finish()
+    }
 }
 
 impl Default for Sheller {
     fn default() -> Self {
         Self {
-            program: parse_program(),
-            args: DEFAULT_METADATA.args.into(),
+            env_program: parse_program(),
+            policy: ShellPolicy::default(),
+            program: None,
+            args: None,
+            shell: None,
             script: String::new(),
+            positional: Vec::new(),
+            strict: false,
+            envs: Vec::new(),
+            options: exec::Options::default(),
         }
     }
 }
@@ -95,7 +401,7 @@ impl Sheller {
     ///
     /// Set the `COMSPEC` environment variable to `program`, and if the environment variable is not set, use `cmd.exe` as the fallback program.
     ///
-    /// Also set the `args` to `["/D", "/S", "/C"]`.
+    /// Also set the `args` to `["/D", "/S", "/C"]`, or the flags of the detected shell family.
     ///
     /// ## Unix
     ///
@@ -103,7 +409,14 @@ impl Sheller {
     ///
     /// Set the `SHELL` environment variable to `program`, and if the environment variable is not set, use `/bin/sh` as the fallback program.
     ///
-    /// Also set the `args` to `["-c"]`.
+    /// Also set the `args` to `["-c"]`, or the flags of the detected shell family.
+    ///
+    /// ## Shell policy
+    ///
+    /// The program from the environment variable is checked with the `ShellPolicy`.
+    /// By default, the flags are picked by the detected shell family such as `-NoProfile -NonInteractive -Command` for PowerShell,
+    /// and an unknown program falls back to the default program with a warning.
+    /// Please see the `Sheller::shell_policy` method to change it.
     ///
     /// # Arguments
     ///
@@ -128,203 +441,1645 @@ impl Sheller {
         }
     }
 
-    /// Returns `std::process::Command` with the shell program and arguments set.
+    /// Use `shell` to run the script, with its program name and flags.
+    ///
+    /// The program is looked up in `PATH`, and the flags are the ones returned by `Shell::flags`.
+    /// Use this to pin a shell regardless of the `SHELL` or `COMSPEC` environment variable.
     ///
     /// # Examples
     ///
     /// ```
-    /// use sheller::Sheller;
+    /// use sheller::{Shell, Sheller};
     ///
-    /// let mut command = Sheller::new("echo hello").build();
-    /// assert!(command.status().unwrap().success());
+    /// # #[cfg(unix)]
+    /// # {
+    /// let command = Sheller::new("echo hello").shell(Shell::Bash).build();
+    /// assert_eq!(command.get_program(), "bash");
+    /// assert_eq!(command.get_args().collect::<Vec<_>>(), ["-c", "echo hello"]);
+    /// # }
     /// ```
     #[must_use]
-    pub fn build(self) -> std::process::Command {
-        let mut command = std::process::Command::new(&self.program);
-        command.args(&self.args);
-        command.arg(self.script);
-        command
+    pub fn shell(mut self, shell: Shell) -> Self {
+        self.program = Some(shell.program().to_string());
+        self.args = None;
+        self.shell = Some(shell);
+        self
     }
 
-    /// Run the shell command and panic if the command failed to run.
+    /// Use `program` to run the script.
+    ///
+    /// If the shell family is detected from the program name, the flags are set to the ones returned by `Shell::flags`.
+    /// Otherwise, the current flags are kept, so set them with the `Sheller::flags` method for a custom interpreter.
     ///
     /// # Examples
-    /// ```
-    /// use sheller::{CommandExt, Sheller};
     ///
-    /// Sheller::new("echo hello").run();
     /// ```
+    /// use sheller::Sheller;
     ///
-    /// # Panics
-    /// Panics if the command failed to run.
-    pub fn run(self) {
-        self.build().run();
+    /// # #[cfg(unix)]
+    /// # {
+    /// let command = Sheller::new("print('hello')")
+    ///     .program("python3")
+    ///     .flags(["-c"])
+    ///     .build();
+    /// assert_eq!(command.get_program(), "python3");
+    /// assert_eq!(command.get_args().collect::<Vec<_>>(), ["-c", "print('hello')"]);
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn program<T>(mut self, program: T) -> Self
+    where
+        T: Into<String>,
+    {
+        let program = program.into();
+        self.shell = Shell::from_program(&program);
+        if self.shell.is_some() {
+            self.args = None;
+        }
+        self.program = Some(program);
+        self
     }
 
-    /// Run the shell command and return a `Result`.
+    /// Set the flags passed to the program before the script, replacing the current ones.
     ///
     /// # Examples
-    /// ```
-    /// use sheller::{CommandExt, Sheller};
     ///
-    /// Sheller::new("echo hello").try_run().unwrap();
     /// ```
+    /// use sheller::{Shell, Sheller};
     ///
-    /// # Errors
-    /// Returns an `Err` if the command failed to run.
-    pub fn try_run(self) -> Result<()> {
-        self.build().try_run()
+    /// let command = Sheller::new("echo hello")
+    ///     .shell(Shell::Bash)
+    ///     .flags(["-e", "-c"])
+    ///     .build();
+    /// assert_eq!(command.get_args().collect::<Vec<_>>(), ["-e", "-c", "echo hello"]);
+    /// ```
+    #[must_use]
+    pub fn flags<I, S>(mut self, flags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args = Some(flags.into_iter().map(Into::into).collect());
+        self
     }
-}
 
-pub trait CommandExt {
-    /// Run the command and panic if the command failed to run.
+    /// Set how the shell program from the `SHELL` or `COMSPEC` environment variable is handled.
+    ///
+    /// It has no effect if the program is set with the `Sheller::shell` or `Sheller::program` method.
+    /// Please see `ShellPolicy` for the choices.
     ///
     /// # Examples
+    ///
     /// ```
-    /// use sheller::CommandExt;
-    /// use std::process::Command;
+    /// use sheller::{ShellPolicy, Sheller};
     ///
-    /// #[cfg(windows)]
-    /// fn example() {
-    ///     let mut command = Command::new("cmd.exe");
-    ///     command.args(["/D", "/S", "/C", "echo hello"]).run();
-    /// }
+    /// Sheller::new("echo hello")
+    ///     .shell_policy(ShellPolicy::Fallback)
+    ///     .run();
+    /// ```
+    #[must_use]
+    pub fn shell_policy(mut self, policy: ShellPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Pass `values` to the script as positional parameters instead of formatting them into the script.
     ///
-    /// #[cfg(unix)]
-    /// fn example() {
-    ///     let mut command = Command::new("echo");
-    ///     command.arg("hello").run();
-    /// }
+    /// The values are appended after the script, so POSIX shells receive them as `$1..$n` (`sh -c script argv0 args...`), and `$0` is set to the program.
+    /// Fish receives them as `$argv`.
+    /// No quoting is involved, so this is the safest way to parameterize a script.
+    ///
+    /// Other shells such as `cmd.exe` and PowerShell have no positional parameters for an inline script.
+    /// Running the script without the values would run a different command, so the command fails to spawn with `Error::Spawn` instead, and `build` panics.
+    ///
+    /// # Examples
     ///
-    /// example();
     /// ```
+    /// use sheller::Sheller;
     ///
-    /// # Panics
-    /// Panics if the command failed to run.
-    fn run(&mut self);
+    /// # #[cfg(unix)]
+    /// # {
+    /// let stdout = Sheller::new("echo \"$1\" \"$2\"")
+    ///     .positional(["hello; rm -rf ~", "world"])
+    ///     .read();
+    /// assert_eq!(stdout, "hello; rm -rf ~ world");
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn positional<I, S>(mut self, values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        self.positional.extend(
+            values
+                .into_iter()
+                .map(|value| value.as_ref().to_os_string()),
+        );
+        self
+    }
 
-    /// Run the command and return a `Result`.
+    /// Make the script exit on the first failing command, on unset variables and, where supported, on failures inside pipelines.
+    ///
+    /// The script is prefixed with the preamble returned by `Shell::strict_preamble` for the shell family, such as `set -eu`.
+    /// If the shell has no such preamble, the script is run as it is and a warning is logged.
     ///
     /// # Examples
+    ///
     /// ```
-    /// use sheller::CommandExt;
-    /// use std::process::Command;
+    /// use sheller::Sheller;
     ///
-    /// #[cfg(windows)]
-    /// fn example() {
-    ///     let mut command = Command::new("cmd.exe");
-    ///     command
-    ///         .args(["/D", "/S", "/C", "echo hello"])
-    ///         .try_run()
-    ///         .unwrap();
-    /// }
+    /// # #[cfg(unix)]
+    /// # {
+    /// let result = Sheller::new("cd /does/not/exist\necho unreachable")
+    ///     .strict()
+    ///     .try_run();
+    /// assert!(result.is_err());
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Terminate the command if it does not exit within `timeout`, and fail with `Error::Timeout`.
     ///
-    /// #[cfg(unix)]
-    /// fn example() {
-    ///     let mut command = Command::new("echo");
-    ///     command.arg("hello").try_run().unwrap();
-    /// }
+    /// On Unix, the command is sent `SIGTERM` first, and `SIGKILL` if it is still running after the grace period.
+    /// On Windows, the command is killed right away.
+    /// It applies to the `run`, `try_run`, `try_output`, `read` and `try_read` methods, but not to the `std::process::Command` returned by `build`.
+    /// The output that was captured, teed or held back until the command was terminated is reported with the error as it is on any other failure.
+    ///
+    /// # Examples
     ///
-    /// example();
     /// ```
+    /// use sheller::{Error, Sheller};
+    /// use std::time::Duration;
     ///
-    /// # Errors
-    /// Returns an `Err` if the command failed to run.
-    fn try_run(&mut self) -> Result<()>;
-}
-
-#[cfg(unix)]
-fn get_signal(a: std::process::ExitStatus) -> Option<i32> {
-    use std::os::unix::process::ExitStatusExt;
-    a.signal()
-}
-
-#[cfg(windows)]
-fn get_signal(_: std::process::ExitStatus) -> Option<i32> {
-    None
-}
+    /// # #[cfg(unix)]
+    /// # {
+    /// let result = Sheller::new("sleep 10")
+    ///     .timeout(Duration::from_millis(100))
+    ///     .try_run();
+    /// assert!(matches!(result, Err(Error::Timeout { .. })));
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.options.timeout = Some(timeout);
+        self
+    }
 
-impl CommandExt for std::process::Command {
-    /// Run the command and panic if the command failed to run.
+    /// Set how long a timed out command is given to exit after the graceful termination before it is killed.
+    ///
+    /// The default is 5 seconds.
+    /// Please see the `Sheller::timeout` method for more information.
     ///
     /// # Examples
+    ///
     /// ```
-    /// use sheller::CommandExt;
-    /// use std::process::Command;
+    /// use sheller::Sheller;
+    /// use std::time::Duration;
     ///
-    /// #[cfg(windows)]
-    /// fn example() {
-    ///     let mut command = Command::new("cmd.exe");
-    ///     command.args(["/D", "/S", "/C", "echo hello"]).run();
-    /// }
+    /// Sheller::new("echo hello")
+    ///     .timeout(Duration::from_secs(60))
+    ///     .grace_period(Duration::from_secs(1))
+    ///     .run();
+    /// ```
+    #[must_use]
+    pub fn grace_period(mut self, grace_period: std::time::Duration) -> Self {
+        self.options.grace_period = grace_period;
+        self
+    }
+
+    /// Spawn the shell in a new process group, and signal the whole group when it is terminated.
     ///
-    /// #[cfg(unix)]
-    /// fn example() {
-    ///     let mut command = Command::new("echo");
-    ///     command.arg("hello").run();
-    /// }
+    /// The shell usually runs the actual workload as its children, and terminating only the shell leaves them running.
+    /// With this option, a timed out command is terminated together with every process it started in the same group.
+    ///
+    /// The new process group is detached from the terminal, so `ctrl + c` in the terminal no longer reaches the command.
+    /// Please see `DropPolicy` for how to keep the group from outliving this process.
+    ///
+    /// This is a Unix-only option, and it has no effect on Windows.
+    ///
+    /// # Examples
     ///
-    /// example();
     /// ```
+    /// use sheller::Sheller;
+    /// use std::time::Duration;
     ///
-    /// # Panics
-    /// Panics if the command failed to run.
-    fn run(&mut self) {
-        self.try_run().unwrap();
+    /// # #[cfg(unix)]
+    /// # {
+    /// let result = Sheller::new("sleep 10 & sleep 10; wait")
+    ///     .process_group()
+    ///     .timeout(Duration::from_millis(100))
+    ///     .try_run();
+    /// assert!(result.is_err());
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn process_group(mut self) -> Self {
+        self.options.process_group = true;
+        self
     }
 
-    /// Run the command and return a `Result` with `Ok` if the command was successful, and `Err` if the command failed.
+    /// Set the exit codes that count as success, instead of only `0`.
+    ///
+    /// This is useful for programs like `grep` that exit with `1` when nothing matched, or `diff` that exits with `1` when the inputs differ.
+    /// Use `try_run_code` to find out which of the codes the command exited with.
     ///
     /// # Examples
+    ///
     /// ```
-    /// use sheller::CommandExt;
-    /// use std::process::Command;
+    /// use sheller::Sheller;
     ///
-    /// #[cfg(windows)]
-    /// fn example() {
-    ///     let mut command = Command::new("cmd.exe");
-    ///     command
-    ///         .args(["/D", "/S", "/C", "echo hello"])
-    ///         .try_run()
-    ///         .unwrap();
-    /// }
+    /// Sheller::new("exit 1").ok_codes([0, 1]).try_run().unwrap();
+    /// assert!(Sheller::new("exit 2").ok_codes([0, 1]).try_run().is_err());
+    /// ```
+    #[must_use]
+    pub fn ok_codes<I>(mut self, codes: I) -> Self
+    where
+        I: IntoIterator<Item = i32>,
+    {
+        self.options.ok_codes = codes.into_iter().collect();
+        self
+    }
+
+    /// Call `callback` with each line the command writes to stdout, while it runs.
     ///
-    /// #[cfg(unix)]
-    /// fn example() {
-    ///     let mut command = Command::new("echo");
-    ///     command.arg("hello").try_run().unwrap();
-    /// }
+    /// stdout is piped to a reader thread that calls `callback` with each line, without the line ending.
+    /// So the lines are not written to the terminal.
+    /// With `try_output`, the lines are captured as well.
+    ///
+    /// # Examples
     ///
-    /// example();
     /// ```
+    /// use sheller::Sheller;
+    /// use std::sync::mpsc;
     ///
-    /// # Errors
-    /// Returns an `Err` if the command failed to run.
-    fn try_run(&mut self) -> Result<()> {
-        info!(command = ?self, "Running command.");
-        let mut command = self.spawn().map_err(|e| {
-            error!(command = ?self, error = ?e, "Failed to spawn command.");
-            e
-        })?;
-        let status = command.wait().map_err(|e| {
-            error!(command = ?self, error = ?e, "Failed to wait for command.");
-            e
-        })?;
-        if let Some(exit_code) = status.code() {
-            if exit_code == 0 {
-                info!(command = ?self, "Succeeded to run command with zero exit code.");
-                Ok(())
-            } else {
-                error!(command = ?self, exit_code = ?exit_code, "Failed to run command with non-zero exit code.");
-                Err(Error::ExitCode(exit_code))
-            }
-        } else if let Some(signal) = get_signal(status) {
-            error!(command = ?self, signal = ?signal, "Failed to run command with signal.");
-            Err(Error::Signal(signal))
-        } else {
-            error!(command = ?self, "Failed to run command with no exit code and signal.");
-            Err(Error::NoExitCodeAndSignal)
+    /// let (sender, receiver) = mpsc::channel();
+    /// Sheller::new("echo hello")
+    ///     .on_stdout_line(move |line| sender.send(line.to_string()).unwrap())
+    ///     .try_run()
+    ///     .unwrap();
+    /// assert_eq!(receiver.recv().unwrap().trim_end(), "hello");
+    /// ```
+    #[must_use]
+    pub fn on_stdout_line<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&str) + Send + 'static,
+    {
+        self.options.on_stdout_line = Some(exec::LineCallback::new(callback));
+        self
+    }
+
+    /// Call `callback` with each line the command writes to stderr, while it runs.
+    ///
+    /// Please see the `on_stdout_line` method for more information.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let lines = Arc::new(Mutex::new(Vec::new()));
+    /// let sink = Arc::clone(&lines);
+    /// let result = Sheller::new("echo oops 1>&2&& exit 1")
+    ///     .on_stderr_line(move |line| sink.lock().unwrap().push(line.trim_end().to_string()))
+    ///     .try_run();
+    /// assert!(result.is_err());
+    /// assert_eq!(*lines.lock().unwrap(), ["oops"]);
+    /// ```
+    #[must_use]
+    pub fn on_stderr_line<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&str) + Send + 'static,
+    {
+        self.options.on_stderr_line = Some(exec::LineCallback::new(callback));
+        self
+    }
+
+    /// Forward the output of the command to `tracing`, logging each line of stdout with `info!` and each line of stderr with `warn!`.
+    ///
+    /// The events have the `sheller::output` target, and are logged in the `run` span of the command, which has its pid and a `run_id` that tells the runs apart.
+    /// stdout and stderr are piped, so the lines are not written to the terminal.
+    /// Use `log_output_levels` to choose the levels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// Sheller::new("echo hello").log_output().run();
+    /// ```
+    #[must_use]
+    pub fn log_output(self) -> Self {
+        self.log_output_levels(Level::Info, Level::Warn)
+    }
+
+    /// Forward the output of the command to `tracing`, logging each line of stdout at `stdout` and each line of stderr at `stderr`.
+    ///
+    /// Please see the `log_output` method for more information.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::{Level, Sheller};
+    ///
+    /// Sheller::new("echo hello")
+    ///     .log_output_levels(Level::Debug, Level::Error)
+    ///     .run();
+    /// ```
+    #[must_use]
+    pub fn log_output_levels(mut self, stdout: Level, stderr: Level) -> Self {
+        self.options.log_output = Some(exec::OutputLevels { stdout, stderr });
+        self
+    }
+
+    /// Write stdout and stderr of the command to the terminal as usual, and capture them at the same time.
+    ///
+    /// If the command fails, everything captured is attached to the error as `Context::stdout_tail` and `Context::stderr_tail`, and the error displays the last lines of both.
+    /// Use `tee_bounded` to limit how much is kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let error = Sheller::new("echo hello&& exit 1")
+    ///     .tee()
+    ///     .try_run()
+    ///     .unwrap_err();
+    /// assert_eq!(error.context().unwrap().stdout_tail.as_deref().map(str::trim_end), Some("hello"));
+    /// ```
+    #[must_use]
+    pub fn tee(mut self) -> Self {
+        self.options.tee = Some(exec::Tee { limit: None });
+        self.options.quiet_unless_failed = false;
+        self
+    }
+
+    /// Like `tee`, but keep only the last `limit` bytes of stdout and of stderr.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let error = Sheller::new("echo hello&& exit 1")
+    ///     .tee_bounded(3)
+    ///     .try_run()
+    ///     .unwrap_err();
+    /// assert_eq!(error.context().unwrap().stdout_tail.as_deref().unwrap().len(), 3);
+    /// ```
+    #[must_use]
+    pub fn tee_bounded(mut self, limit: usize) -> Self {
+        self.options.tee = Some(exec::Tee { limit: Some(limit) });
+        self.options.quiet_unless_failed = false;
+        self
+    }
+
+    /// Hide the output of the command, and write it to stderr after the error only if the command fails, like `ninja` does.
+    ///
+    /// stdout and stderr are held back together, in the order they were read, even when they are captured by `try_output`.
+    /// They are written on any failure, including a timeout.
+    /// It replaces `tee`, and `tee` replaces it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// // Prints nothing.
+    /// Sheller::new("echo hello").quiet_unless_failed().run();
+    ///
+    /// // Prints the error, then `oops`, to stderr.
+    /// let result = Sheller::new("echo oops&& exit 1")
+    ///     .quiet_unless_failed()
+    ///     .try_run();
+    /// assert!(result.is_err());
+    /// ```
+    #[must_use]
+    pub fn quiet_unless_failed(mut self) -> Self {
+        self.options.quiet_unless_failed = true;
+        self.options.tee = None;
+        self
+    }
+
+    /// Set the levels that the start, the success and the failure of the command are logged at.
+    ///
+    /// Without it, the levels set by `set_log_levels` are used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::{LogLevels, Sheller};
+    ///
+    /// Sheller::new("echo hello").log_levels(LogLevels::SILENT).run();
+    /// ```
+    #[must_use]
+    pub fn log_levels(mut self, levels: LogLevels) -> Self {
+        self.options.log_levels = Some(levels);
+        self
+    }
+
+    /// Mark `value` as a secret, so that it is replaced with `***` wherever the command is logged or rendered.
+    ///
+    /// It applies to the events of the command and its logged output, the `Display` of its errors, and the `Debug` of the `Sheller`.
+    /// The command itself still receives `value` as it is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let token = "hunter2";
+    /// let error = Sheller::new(format!("echo {token} && exit 1"))
+    ///     .secret(token)
+    ///     .try_run()
+    ///     .unwrap_err();
+    /// assert!(!error.to_string().contains(token));
+    /// ```
+    #[must_use]
+    pub fn secret<T>(mut self, value: T) -> Self
+    where
+        T: std::fmt::Display,
+    {
+        let value = value.to_string();
+        let quoted = self.quote(&value);
+        self.options.secrets.add(&value);
+        self.options.secrets.add(&quoted);
+        self
+    }
+
+    /// Set the environment variable `key` of the command to `value`, and mark `value` as a secret.
+    ///
+    /// Please see the `secret` method for where it is replaced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// # #[cfg(unix)]
+    /// # {
+    /// let stdout = Sheller::new("echo $TOKEN")
+    ///     .env_secret("TOKEN", "hunter2")
+    ///     .read();
+    /// assert_eq!(stdout, "hunter2");
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn env_secret<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: AsRef<std::ffi::OsStr>,
+        V: std::fmt::Display,
+    {
+        let value = value.to_string();
+        self.options.secrets.add(&value);
+        self.envs.push((key.as_ref().to_os_string(), value));
+        self
+    }
+
+    /// Redirect stdout of the command to the file at `path`, truncating it, like `> path`.
+    ///
+    /// The file is opened when the command is spawned, and failing to open it fails the command with `Error::Spawn`.
+    /// Redirections are applied in the order they are added, and are appended to the logged command.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let path = std::env::temp_dir().join("sheller-stdout-to-file.log");
+    /// Sheller::new("echo hello").stdout_to_file(&path).run();
+    /// assert_eq!(std::fs::read_to_string(&path).unwrap().trim_end(), "hello");
+    /// ```
+    #[must_use]
+    pub fn stdout_to_file<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<std::path::Path>,
+    {
+        self.options.redirects.push(exec::Redirect::StdoutToFile {
+            path: path.as_ref().to_path_buf(),
+            append: false,
+        });
+        self
+    }
+
+    /// Redirect stdout of the command to the end of the file at `path`, like `>> path`.
+    ///
+    /// Please see the `stdout_to_file` method for more information.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let path = std::env::temp_dir().join("sheller-append-stdout.log");
+    /// Sheller::new("echo hello").stdout_to_file(&path).run();
+    /// Sheller::new("echo world").append_stdout(&path).run();
+    /// assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+    /// ```
+    #[must_use]
+    pub fn append_stdout<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<std::path::Path>,
+    {
+        self.options.redirects.push(exec::Redirect::StdoutToFile {
+            path: path.as_ref().to_path_buf(),
+            append: true,
+        });
+        self
+    }
+
+    /// Redirect stderr of the command to where its stdout goes, like `2>&1`.
+    ///
+    /// If stdout is captured, as by `try_output`, or piped to the next stage of a `Pipeline`, stderr is interleaved into it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let stdout = Sheller::new("echo oops 1>&2").stderr_to_stdout().read();
+    /// assert_eq!(stdout, "oops");
+    /// ```
+    #[must_use]
+    pub fn stderr_to_stdout(mut self) -> Self {
+        self.options.redirects.push(exec::Redirect::StderrToStdout);
+        self
+    }
+
+    /// Read stdin of the command from the file at `path`, like `< path`.
+    ///
+    /// Please see the `stdout_to_file` method for more information.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let path = std::env::temp_dir().join("sheller-stdin-from-file.txt");
+    /// std::fs::write(&path, "hello").unwrap();
+    /// # #[cfg(unix)]
+    /// assert_eq!(Sheller::new("cat").stdin_from_file(&path).read(), "hello");
+    /// ```
+    #[must_use]
+    pub fn stdin_from_file<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<std::path::Path>,
+    {
+        self.options
+            .redirects
+            .push(exec::Redirect::StdinFromFile(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Write `bytes` to stdin of the command, and close it, like `< file` with `bytes` in the file.
+    ///
+    /// It is the same as the `stdin_bytes` method.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// # #[cfg(unix)]
+    /// assert_eq!(Sheller::new("cat").stdin_from_bytes("hello").read(), "hello");
+    /// ```
+    #[must_use]
+    pub fn stdin_from_bytes<B>(self, bytes: B) -> Self
+    where
+        B: Into<Vec<u8>>,
+    {
+        self.stdin_bytes(bytes)
+    }
+
+    /// Write `bytes` to stdin of the command, and close it.
+    ///
+    /// The input is written from a thread while the command runs, so a command that writes a lot of output before reading all of its input does not deadlock.
+    /// A command that exits without reading all of its input does not fail because of it, but an error writing it fails `try_run` and `try_output`.
+    /// Like the redirections, it replaces any stdin set before it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// # #[cfg(unix)]
+    /// assert_eq!(Sheller::new("cat").stdin_bytes("hello").read(), "hello");
+    /// ```
+    #[must_use]
+    pub fn stdin_bytes<B>(self, bytes: B) -> Self
+    where
+        B: Into<Vec<u8>>,
+    {
+        self.stdin_input(exec::Input::Bytes(bytes.into()))
+    }
+
+    /// Copy everything `reader` reads to stdin of the command, and close it.
+    ///
+    /// Please see the `stdin_bytes` method for more information.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let reader = std::io::Cursor::new("hello");
+    /// # #[cfg(unix)]
+    /// assert_eq!(Sheller::new("cat").stdin_reader(reader).read(), "hello");
+    /// ```
+    #[must_use]
+    pub fn stdin_reader<R>(self, reader: R) -> Self
+    where
+        R: std::io::Read + Send + 'static,
+    {
+        self.stdin_input(exec::Input::Reader(exec::TakeOnce::new(Box::new(reader))))
+    }
+
+    /// Write each of `lines` followed by `\n` to stdin of the command, and close it.
+    ///
+    /// The lines are produced on the writer thread as the command reads them.
+    /// Please see the `stdin_bytes` method for more information.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// # #[cfg(unix)]
+    /// # {
+    /// let stdout = Sheller::new("sort").stdin_lines(["b", "a"]).read();
+    /// assert_eq!(stdout, "a\nb");
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn stdin_lines<I>(self, lines: I) -> Self
+    where
+        I: IntoIterator,
+        I::IntoIter: Send + 'static,
+        I::Item: std::fmt::Display,
+    {
+        let lines = lines.into_iter().map(|line| line.to_string());
+        self.stdin_input(exec::Input::Lines(exec::TakeOnce::new(Box::new(lines))))
+    }
+
+    fn stdin_input(mut self, input: exec::Input) -> Self {
+        self.options
+            .redirects
+            .push(exec::Redirect::StdinFrom(input));
+        self
+    }
+
+    /// Append `value` to the script as a single word, quoted for the shell program.
+    ///
+    /// A space is inserted before `value` unless the script is empty.
+    /// Please see the `Shell::quote` method for the quoting rules.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let stdout = Sheller::new("echo").arg_quoted("hello; echo world").read();
+    /// # #[cfg(unix)]
+    /// assert_eq!(stdout, "hello; echo world");
+    /// ```
+    #[must_use]
+    pub fn arg_quoted<T>(mut self, value: T) -> Self
+    where
+        T: std::fmt::Display,
+    {
+        let quoted = self.quote(&value);
+        if !self.script.is_empty() {
+            self.script.push(' ');
         }
+        self.script.push_str(&quoted);
+        self
+    }
+
+    /// Returns `value` quoted for the shell program, so that it is read back as a single literal word.
+    ///
+    /// The shell family is detected from the program name, and if it is unknown, the platform default is used.
+    /// Please see the `Shell::quote` method for the quoting rules.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let sheller = Sheller::default();
+    /// assert_eq!(sheller.quote("hello"), "hello");
+    /// ```
+    #[must_use]
+    pub fn quote<T>(&self, value: &T) -> String
+    where
+        T: std::fmt::Display + ?Sized,
+    {
+        self.shell_family().quote(&value.to_string())
+    }
+
+    fn shell_family(&self) -> Shell {
+        self.resolve().shell
+    }
+
+    fn resolve(&self) -> Invocation {
+        let default_program = || DEFAULT_METADATA.program.to_string();
+        let (program, detected, rejected) = match (&self.program, &self.env_program) {
+            (Some(program), _) => (
+                program.clone(),
+                self.shell.or_else(|| Shell::from_program(program)),
+                None,
+            ),
+            (None, Some(env_program)) => match (self.policy, Shell::from_program(env_program)) {
+                (ShellPolicy::Trust, _) => (env_program.clone(), None, None),
+                (ShellPolicy::Adapt, Some(shell)) => (env_program.clone(), Some(shell), None),
+                (ShellPolicy::Fallback, Some(shell)) if is_default_compatible(shell) => {
+                    (env_program.clone(), Some(shell), None)
+                }
+                _ => (
+                    default_program(),
+                    Some(DEFAULT_METADATA.shell),
+                    Some(env_program.clone()),
+                ),
+            },
+            (None, None) => (default_program(), Some(DEFAULT_METADATA.shell), None),
+        };
+        let args = self
+            .args
+            .clone()
+            .unwrap_or_else(|| to_strings(detected.unwrap_or(DEFAULT_METADATA.shell).flags()));
+        let shell = detected
+            .or_else(|| Shell::from_program(&program))
+            .unwrap_or(DEFAULT_METADATA.shell);
+        Invocation {
+            program,
+            args,
+            shell,
+            rejected,
+        }
+    }
+
+    /// Returns `std::process::Command` with the shell program and arguments set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let mut command = Sheller::new("echo hello").build();
+    /// assert!(command.status().unwrap().success());
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if positional parameters are set for a shell that does not support them.
+    #[must_use]
+    pub fn build(self) -> std::process::Command {
+        let (command, options) = self.into_parts();
+        if let Some(invalid) = options.invalid {
+            panic!("{invalid}");
+        }
+        command
+    }
+
+    fn into_parts(mut self) -> (std::process::Command, exec::Options) {
+        let invocation = self.resolve();
+        if let Some(rejected) = &invocation.rejected {
+            event!(
+                Level::Warn,
+                rejected_program = rejected,
+                program = invocation.program,
+                policy = ?self.policy,
+                "Shell program from environment variable is not supported, falling back to default program."
+            );
+        }
+        let mut command = std::process::Command::new(&invocation.program);
+        command.args(&invocation.args);
+        command.envs(self.envs.iter().map(|(key, value)| (key, value)));
+        let script = match (self.strict, invocation.shell.strict_preamble()) {
+            (true, Some(preamble)) => {
+                std::borrow::Cow::Owned(format!("{preamble}\n{}", self.script))
+            }
+            (true, None) => {
+                event!(
+                    Level::Warn,
+                    program = invocation.program,
+                    shell = ?invocation.shell,
+                    "Shell has no strict mode preamble, running script as it is."
+                );
+                std::borrow::Cow::Borrowed(&self.script)
+            }
+            (false, _) => std::borrow::Cow::Borrowed(&self.script),
+        };
+        push_script(&mut command, &invocation, &script);
+        if !self.positional.is_empty() {
+            match invocation.shell {
+                Shell::Sh | Shell::Bash | Shell::Zsh | Shell::Dash | Shell::Ksh => {
+                    command.arg(&invocation.program);
+                    command.args(&self.positional);
+                }
+                Shell::Fish | Shell::Elvish => {
+                    command.args(&self.positional);
+                }
+                shell @ (Shell::Cmd | Shell::PowerShell | Shell::Nu | Shell::Xonsh) => {
+                    self.options.invalid = Some(format!(
+                        "Shell {shell:?} does not support positional parameters."
+                    ));
+                }
+            }
+        }
+        (command, self.options)
+    }
+
+    /// Run the shell command and panic if the command failed to run.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::{CommandExt, Sheller};
+    ///
+    /// Sheller::new("echo hello").run();
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the command failed to run.
+    pub fn run(self) {
+        self.try_run().unwrap();
+    }
+
+    /// Run the shell command and return a `Result`.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::{CommandExt, Sheller};
+    ///
+    /// Sheller::new("echo hello").try_run().unwrap();
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run.
+    pub fn try_run(self) -> Result<()> {
+        let (mut command, options) = self.into_parts();
+        exec::run(&mut command, &options)?;
+        Ok(())
+    }
+
+    /// Run the shell command and return its exit code if it is one of the accepted exit codes.
+    ///
+    /// Please see the `ok_codes` method for setting the accepted exit codes.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let code = Sheller::new("exit 1").ok_codes([0, 1]).try_run_code().unwrap();
+    /// assert_eq!(code, 1);
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run or exited with a code that is not accepted.
+    pub fn try_run_code(self) -> Result<i32> {
+        let (mut command, options) = self.into_parts();
+        exec::run(&mut command, &options)
+    }
+
+    /// Run the shell command and translate its exit code with `map`.
+    ///
+    /// The command succeeds if `map` returns `Some`, and fails with `Error::ExitCode` if it returns `None`.
+    /// The accepted exit codes set by `ok_codes` are not used.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// #[derive(Debug, PartialEq)]
+    /// enum Diff {
+    ///     Same,
+    ///     Different,
+    /// }
+    ///
+    /// let diff = Sheller::new("exit 1")
+    ///     .try_run_map(|code| match code {
+    ///         0 => Some(Diff::Same),
+    ///         1 => Some(Diff::Different),
+    ///         _ => None,
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(diff, Diff::Different);
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run or `map` returned `None` for its exit code.
+    pub fn try_run_map<T, F>(self, map: F) -> Result<T>
+    where
+        F: FnOnce(i32) -> Option<T>,
+    {
+        let (mut command, options) = self.into_parts();
+        exec::run_map(&mut command, &options, map)
+    }
+
+    /// Spawn the shell command in the background and return a `Handle` to it.
+    ///
+    /// The timeout and the process group option apply to the handle as well.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let mut handle = Sheller::new("echo hello").spawn().unwrap();
+    /// // Do something else while the command is running.
+    /// handle.wait().unwrap();
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to spawn.
+    pub fn spawn(self) -> Result<Handle> {
+        let (mut command, options) = self.into_parts();
+        Handle::spawn(&mut command, options)
+    }
+
+    /// Run the shell command, capture its stdout and stderr, and return an `Output`.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// let output = Sheller::new("echo hello").try_output().unwrap();
+    /// assert!(output.status.success());
+    /// assert_eq!(output.stdout_string().unwrap(), "hello");
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run.
+    pub fn try_output(self) -> Result<Output> {
+        let (mut command, options) = self.into_parts();
+        exec::output(&mut command, &options)
+    }
+
+    /// Run the shell command and return its stdout without the trailing newline, and panic if the command failed to run.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// assert_eq!(Sheller::new("echo hello").read(), "hello");
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the command failed to run or stdout is not valid UTF-8.
+    #[must_use]
+    pub fn read(self) -> String {
+        self.try_read().unwrap()
+    }
+
+    /// Run the shell command and return its stdout without the trailing newline as a `Result`.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// assert_eq!(Sheller::new("echo hello").try_read().unwrap(), "hello");
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run or stdout is not valid UTF-8.
+    pub fn try_read(self) -> Result<String> {
+        self.try_output()?.stdout_string()
+    }
+}
+
+pub trait CommandExt {
+    /// Run the command and panic if the command failed to run.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::CommandExt;
+    /// use std::process::Command;
+    ///
+    /// #[cfg(windows)]
+    /// fn example() {
+    ///     let mut command = Command::new("cmd.exe");
+    ///     command.args(["/D", "/S", "/C", "echo hello"]).run();
+    /// }
+    ///
+    /// #[cfg(unix)]
+    /// fn example() {
+    ///     let mut command = Command::new("echo");
+    ///     command.arg("hello").run();
+    /// }
+    ///
+    /// example();
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the command failed to run.
+    fn run(&mut self);
+
+    /// Run the command and return a `Result`.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::CommandExt;
+    /// use std::process::Command;
+    ///
+    /// #[cfg(windows)]
+    /// fn example() {
+    ///     let mut command = Command::new("cmd.exe");
+    ///     command
+    ///         .args(["/D", "/S", "/C", "echo hello"])
+    ///         .try_run()
+    ///         .unwrap();
+    /// }
+    ///
+    /// #[cfg(unix)]
+    /// fn example() {
+    ///     let mut command = Command::new("echo");
+    ///     command.arg("hello").try_run().unwrap();
+    /// }
+    ///
+    /// example();
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run.
+    fn try_run(&mut self) -> Result<()>;
+
+    /// Run the command and return its exit code if it is one of `codes`.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::CommandExt;
+    /// use std::process::Command;
+    ///
+    /// #[cfg(windows)]
+    /// fn example() {
+    ///     let mut command = Command::new("cmd.exe");
+    ///     let code = command
+    ///         .args(["/D", "/S", "/C", "exit 1"])
+    ///         .try_run_accepting(&[0, 1])
+    ///         .unwrap();
+    ///     assert_eq!(code, 1);
+    /// }
+    ///
+    /// #[cfg(unix)]
+    /// fn example() {
+    ///     let mut command = Command::new("sh");
+    ///     let code = command
+    ///         .args(["-c", "exit 1"])
+    ///         .try_run_accepting(&[0, 1])
+    ///         .unwrap();
+    ///     assert_eq!(code, 1);
+    /// }
+    ///
+    /// example();
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run or exited with a code that is not in `codes`.
+    fn try_run_accepting(&mut self, codes: &[i32]) -> Result<i32>;
+
+    /// Run the command and translate its exit code with `map`.
+    ///
+    /// The command succeeds if `map` returns `Some`, and fails with `Error::ExitCode` if it returns `None`.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::CommandExt;
+    /// use std::process::Command;
+    ///
+    /// #[cfg(windows)]
+    /// fn example() {
+    ///     let mut command = Command::new("cmd.exe");
+    ///     let found = command
+    ///         .args(["/D", "/S", "/C", "exit 1"])
+    ///         .try_run_map(|code| [true, false].get(code as usize).copied())
+    ///         .unwrap();
+    ///     assert!(!found);
+    /// }
+    ///
+    /// #[cfg(unix)]
+    /// fn example() {
+    ///     let mut command = Command::new("grep");
+    ///     let found = command
+    ///         .args(["-q", "needle", "/dev/null"])
+    ///         .try_run_map(|code| [true, false].get(code as usize).copied())
+    ///         .unwrap();
+    ///     assert!(!found);
+    /// }
+    ///
+    /// example();
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run or `map` returned `None` for its exit code.
+    fn try_run_map<T, F>(&mut self, map: F) -> Result<T>
+    where
+        F: FnOnce(i32) -> Option<T>;
+
+    /// Run the command, capture its stdout and stderr, and return an `Output`.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::CommandExt;
+    /// use std::process::Command;
+    ///
+    /// #[cfg(windows)]
+    /// fn example() {
+    ///     let mut command = Command::new("cmd.exe");
+    ///     let output = command
+    ///         .args(["/D", "/S", "/C", "echo hello"])
+    ///         .try_output()
+    ///         .unwrap();
+    ///     assert!(output.status.success());
+    /// }
+    ///
+    /// #[cfg(unix)]
+    /// fn example() {
+    ///     let mut command = Command::new("echo");
+    ///     let output = command.arg("hello").try_output().unwrap();
+    ///     assert_eq!(output.stdout, b"hello\n");
+    /// }
+    ///
+    /// example();
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run.
+    fn try_output(&mut self) -> Result<Output>;
+
+    /// Spawn the command in the background and return a `Handle` to it.
+    ///
+    /// Unlike `std::process::Command::spawn`, the `Handle` kills the command when it is dropped, and its result is classified like `try_run`.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::CommandExt;
+    /// use std::process::Command;
+    ///
+    /// #[cfg(windows)]
+    /// fn example() {
+    ///     let mut command = Command::new("cmd.exe");
+    ///     let mut handle = command
+    ///         .args(["/D", "/S", "/C", "echo hello"])
+    ///         .spawn_managed()
+    ///         .unwrap();
+    ///     handle.wait().unwrap();
+    /// }
+    ///
+    /// #[cfg(unix)]
+    /// fn example() {
+    ///     let mut command = Command::new("echo");
+    ///     let mut handle = command.arg("hello").spawn_managed().unwrap();
+    ///     handle.wait().unwrap();
+    /// }
+    ///
+    /// example();
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to spawn.
+    fn spawn_managed(&mut self) -> Result<Handle>;
+
+    /// Run the command and return a `Result`, terminating the command if it does not exit within `timeout`.
+    ///
+    /// On Unix, the command is sent `SIGTERM` first, and `SIGKILL` if it is still running after `grace_period`.
+    /// On Windows, the command is killed right away.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::{CommandExt, Error};
+    /// use std::{process::Command, time::Duration};
+    ///
+    /// #[cfg(windows)]
+    /// fn example() {
+    ///     let mut command = Command::new("cmd.exe");
+    ///     command
+    ///         .args(["/D", "/S", "/C", "echo hello"])
+    ///         .try_run_with_timeout(Duration::from_secs(10), Duration::from_secs(1))
+    ///         .unwrap();
+    /// }
+    ///
+    /// #[cfg(unix)]
+    /// fn example() {
+    ///     let mut command = Command::new("sleep");
+    ///     let result = command
+    ///         .arg("10")
+    ///         .try_run_with_timeout(Duration::from_millis(100), Duration::from_secs(1));
+    ///     assert!(matches!(result, Err(Error::Timeout { forced: false, .. })));
+    /// }
+    ///
+    /// example();
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run, or `Error::Timeout` if it timed out.
+    fn try_run_with_timeout(
+        &mut self,
+        timeout: std::time::Duration,
+        grace_period: std::time::Duration,
+    ) -> Result<()>;
+
+    /// Run the command with its stdout and stderr written to the terminal and captured at the same time, and return a `Result`.
+    ///
+    /// If the command fails, what was captured is attached to the error, keeping the last `limit` bytes of each stream if it is `Some`.
+    /// Please see the `Sheller::tee` method for more information.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::CommandExt;
+    /// use std::process::Command;
+    ///
+    /// #[cfg(windows)]
+    /// fn example() {
+    ///     let mut command = Command::new("cmd.exe");
+    ///     let error = command
+    ///         .args(["/D", "/S", "/C", "echo oops 1>&2&& exit 1"])
+    ///         .try_run_tee(None)
+    ///         .unwrap_err();
+    ///     assert!(error.context().unwrap().stderr_tail.is_some());
+    /// }
+    ///
+    /// #[cfg(unix)]
+    /// fn example() {
+    ///     let mut command = Command::new("sh");
+    ///     let error = command
+    ///         .args(["-c", "echo oops 1>&2; exit 1"])
+    ///         .try_run_tee(None)
+    ///         .unwrap_err();
+    ///     assert_eq!(error.context().unwrap().stderr_tail.as_deref(), Some("oops\n"));
+    /// }
+    ///
+    /// example();
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run.
+    fn try_run_tee(&mut self, limit: Option<usize>) -> Result<()>;
+
+    /// Run the command and return its stdout without the trailing newline, and panic if the command failed to run.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::CommandExt;
+    /// use std::process::Command;
+    ///
+    /// #[cfg(windows)]
+    /// fn example() {
+    ///     let mut command = Command::new("cmd.exe");
+    ///     assert_eq!(command.args(["/D", "/S", "/C", "echo hello"]).read(), "hello");
+    /// }
+    ///
+    /// #[cfg(unix)]
+    /// fn example() {
+    ///     let mut command = Command::new("echo");
+    ///     assert_eq!(command.arg("hello").read(), "hello");
+    /// }
+    ///
+    /// example();
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the command failed to run or stdout is not valid UTF-8.
+    fn read(&mut self) -> String;
+
+    /// Run the command and return its stdout without the trailing newline as a `Result`.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::CommandExt;
+    /// use std::process::Command;
+    ///
+    /// #[cfg(windows)]
+    /// fn example() {
+    ///     let mut command = Command::new("cmd.exe");
+    ///     let stdout = command
+    ///         .args(["/D", "/S", "/C", "echo hello"])
+    ///         .try_read()
+    ///         .unwrap();
+    ///     assert_eq!(stdout, "hello");
+    /// }
+    ///
+    /// #[cfg(unix)]
+    /// fn example() {
+    ///     let mut command = Command::new("echo");
+    ///     assert_eq!(command.arg("hello").try_read().unwrap(), "hello");
+    /// }
+    ///
+    /// example();
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run or stdout is not valid UTF-8.
+    fn try_read(&mut self) -> Result<String>;
+
+    /// Redirect stdout of the command to the file at `path`, truncating it, like `> path`.
+    ///
+    /// The file is opened right away.
+    /// Unlike the `Sheller` method, the redirection is not shown in the logged command, because `std::process::Command` does not expose its stdio.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::CommandExt;
+    /// use std::process::Command;
+    ///
+    /// let path = std::env::temp_dir().join("sheller-command-stdout-to-file.log");
+    ///
+    /// #[cfg(windows)]
+    /// fn example(path: &std::path::Path) {
+    ///     let mut command = Command::new("cmd.exe");
+    ///     command
+    ///         .args(["/D", "/S", "/C", "echo hello"])
+    ///         .stdout_to_file(path)
+    ///         .unwrap()
+    ///         .run();
+    /// }
+    ///
+    /// #[cfg(unix)]
+    /// fn example(path: &std::path::Path) {
+    ///     let mut command = Command::new("echo");
+    ///     command.arg("hello").stdout_to_file(path).unwrap().run();
+    /// }
+    ///
+    /// example(&path);
+    /// assert_eq!(std::fs::read_to_string(&path).unwrap().trim_end(), "hello");
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the file could not be opened.
+    fn stdout_to_file<P>(&mut self, path: P) -> std::io::Result<&mut Self>
+    where
+        P: AsRef<std::path::Path>;
+
+    /// Redirect stdout of the command to the end of the file at `path`, like `>> path`.
+    ///
+    /// Please see the `stdout_to_file` method for more information.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the file could not be opened.
+    fn append_stdout<P>(&mut self, path: P) -> std::io::Result<&mut Self>
+    where
+        P: AsRef<std::path::Path>;
+
+    /// Redirect stderr of the command to where its stdout goes when it is spawned, like `2>&1`.
+    ///
+    /// On Unix, stderr of the child is made a duplicate of its stdout, so it follows stdout to a file, a pipe or the captured output of `try_output`.
+    /// On Windows, where `std::process::Command` does not expose where its stdout goes, stderr goes to stdout of the current process.
+    /// The redirection is logged when it is added, because it is not shown in the logged command.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::CommandExt;
+    /// use std::process::Command;
+    ///
+    /// #[cfg(windows)]
+    /// fn example() {
+    ///     let mut command = Command::new("cmd.exe");
+    ///     command
+    ///         .args(["/D", "/S", "/C", "echo oops 1>&2"])
+    ///         .stderr_to_stdout()
+    ///         .run();
+    /// }
+    ///
+    /// #[cfg(unix)]
+    /// fn example() {
+    ///     let mut command = Command::new("sh");
+    ///     command.args(["-c", "echo oops 1>&2"]).stderr_to_stdout().run();
+    /// }
+    ///
+    /// example();
+    /// ```
+    fn stderr_to_stdout(&mut self) -> &mut Self;
+
+    /// Read stdin of the command from the file at `path`, like `< path`.
+    ///
+    /// Please see the `stdout_to_file` method for more information.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the file could not be opened.
+    fn stdin_from_file<P>(&mut self, path: P) -> std::io::Result<&mut Self>
+    where
+        P: AsRef<std::path::Path>;
+
+    /// Write `bytes` to stdin of the command from a thread, and close it.
+    ///
+    /// The bytes are written to a pipe right away, so they are read by the next run of the command only.
+    /// A command that exits without reading all of them does not fail because of it.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::CommandExt;
+    /// use std::process::Command;
+    ///
+    /// #[cfg(windows)]
+    /// fn example() {
+    ///     let mut command = Command::new("cmd.exe");
+    ///     let stdout = command
+    ///         .args(["/D", "/S", "/C", "findstr ."])
+    ///         .stdin_from_bytes("hello")
+    ///         .unwrap()
+    ///         .read();
+    ///     assert_eq!(stdout, "hello");
+    /// }
+    ///
+    /// #[cfg(unix)]
+    /// fn example() {
+    ///     let mut command = Command::new("cat");
+    ///     assert_eq!(command.stdin_from_bytes("hello").unwrap().read(), "hello");
+    /// }
+    ///
+    /// example();
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the pipe could not be created.
+    fn stdin_from_bytes<B>(&mut self, bytes: B) -> std::io::Result<&mut Self>
+    where
+        B: Into<Vec<u8>>;
+}
+
+impl CommandExt for std::process::Command {
+    /// Run the command and panic if the command failed to run.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::CommandExt;
+    /// use std::process::Command;
+    ///
+    /// #[cfg(windows)]
+    /// fn example() {
+    ///     let mut command = Command::new("cmd.exe");
+    ///     command.args(["/D", "/S", "/C", "echo hello"]).run();
+    /// }
+    ///
+    /// #[cfg(unix)]
+    /// fn example() {
+    ///     let mut command = Command::new("echo");
+    ///     command.arg("hello").run();
+    /// }
+    ///
+    /// example();
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the command failed to run.
+    fn run(&mut self) {
+        self.try_run().unwrap();
+    }
+
+    /// Run the command and return a `Result` with `Ok` if the command was successful, and `Err` if the command failed.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::CommandExt;
+    /// use std::process::Command;
+    ///
+    /// #[cfg(windows)]
+    /// fn example() {
+    ///     let mut command = Command::new("cmd.exe");
+    ///     command
+    ///         .args(["/D", "/S", "/C", "echo hello"])
+    ///         .try_run()
+    ///         .unwrap();
+    /// }
+    ///
+    /// #[cfg(unix)]
+    /// fn example() {
+    ///     let mut command = Command::new("echo");
+    ///     command.arg("hello").try_run().unwrap();
+    /// }
+    ///
+    /// example();
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run.
+    fn try_run(&mut self) -> Result<()> {
+        exec::run(self, &exec::Options::default())?;
+        Ok(())
+    }
+
+    /// Run the command and return its exit code if it is one of `codes`.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run or exited with a code that is not in `codes`.
+    fn try_run_accepting(&mut self, codes: &[i32]) -> Result<i32> {
+        let options = exec::Options {
+            ok_codes: codes.to_vec(),
+            ..Default::default()
+        };
+        exec::run(self, &options)
+    }
+
+    /// Run the command and translate its exit code with `map`.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run or `map` returned `None` for its exit code.
+    fn try_run_map<T, F>(&mut self, map: F) -> Result<T>
+    where
+        F: FnOnce(i32) -> Option<T>,
+    {
+        exec::run_map(self, &exec::Options::default(), map)
+    }
+
+    /// Run the command, capture its stdout and stderr, and return an `Output`.
+    ///
+    /// As with `std::process::Command::output`, stdout and stderr are piped unless they were already configured on the command, and the command itself is left as it is.
+    /// If the command fails, the last lines of the captured stderr are attached to the error.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run.
+    fn try_output(&mut self) -> Result<Output> {
+        exec::output_configured(self, &exec::Options::default())
+    }
+
+    /// Spawn the command in the background and return a `Handle` to it.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to spawn.
+    fn spawn_managed(&mut self) -> Result<Handle> {
+        Handle::spawn(self, exec::Options::default())
+    }
+
+    /// Run the command and terminate it if it does not exit within `timeout`.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run, or `Error::Timeout` if it timed out.
+    fn try_run_with_timeout(
+        &mut self,
+        timeout: std::time::Duration,
+        grace_period: std::time::Duration,
+    ) -> Result<()> {
+        let options = exec::Options {
+            timeout: Some(timeout),
+            grace_period,
+            ..Default::default()
+        };
+        exec::run(self, &options)?;
+        Ok(())
+    }
+
+    /// Run the command with its stdout and stderr teed, and return a `Result`.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run.
+    fn try_run_tee(&mut self, limit: Option<usize>) -> Result<()> {
+        let options = exec::Options {
+            tee: Some(exec::Tee { limit }),
+            ..Default::default()
+        };
+        exec::run(self, &options)?;
+        Ok(())
+    }
+
+    /// Run the command and return its stdout without the trailing newline, and panic if the command failed to run.
+    ///
+    /// # Panics
+    /// Panics if the command failed to run or stdout is not valid UTF-8.
+    fn read(&mut self) -> String {
+        self.try_read().unwrap()
+    }
+
+    /// Run the command and return its stdout without the trailing newline as a `Result`.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run or stdout is not valid UTF-8.
+    fn try_read(&mut self) -> Result<String> {
+        self.try_output()?.stdout_string()
+    }
+
+    fn stdout_to_file<P>(&mut self, path: P) -> std::io::Result<&mut Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        Ok(self.stdout(exec::open_stdout(path.as_ref(), false)?))
+    }
+
+    fn append_stdout<P>(&mut self, path: P) -> std::io::Result<&mut Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        Ok(self.stdout(exec::open_stdout(path.as_ref(), true)?))
+    }
+
+    fn stderr_to_stdout(&mut self) -> &mut Self {
+        event!(Level::Debug, command = ?self, "Redirecting stderr of command to its stdout.");
+        exec::stderr_to_stdout(self);
+        self
+    }
+
+    fn stdin_from_file<P>(&mut self, path: P) -> std::io::Result<&mut Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        Ok(self.stdin(std::fs::File::open(path)?))
+    }
+
+    fn stdin_from_bytes<B>(&mut self, bytes: B) -> std::io::Result<&mut Self>
+    where
+        B: Into<Vec<u8>>,
+    {
+        Ok(self.stdin(exec::feed(bytes.into())?))
     }
 }
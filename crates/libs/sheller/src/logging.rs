@@ -0,0 +1,166 @@
+use crate::Signal;
+use std::{
+    ffi::OsStr,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        PoisonError, RwLock,
+    },
+    time::Duration,
+};
+use tracing::{field, info_span, Span};
+
+/// The level of an event that sheller logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+/// The levels that sheller logs the start, the success and the failure of commands at.
+///
+/// `None` turns the events off.
+///
+/// # Examples
+/// ```
+/// use sheller::{Level, LogLevels, Sheller};
+///
+/// // Only log failures, and as warnings.
+/// let levels = LogLevels {
+///     start: None,
+///     success: None,
+///     failure: Some(Level::Warn),
+/// };
+/// Sheller::new("echo hello").log_levels(levels).run();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LogLevels {
+    /// The level of "Running command." and "Spawning command."
+    pub start: Option<Level>,
+    /// The level of "Succeeded to run command ..."
+    pub success: Option<Level>,
+    /// The level of "Failed to ..." and of timeouts.
+    pub failure: Option<Level>,
+}
+
+impl LogLevels {
+    /// `info` for the start and the success, and `error` for the failure of commands.
+    pub const DEFAULT: Self = Self {
+        start: Some(Level::Info),
+        success: Some(Level::Info),
+        failure: Some(Level::Error),
+    };
+
+    /// Nothing is logged about commands.
+    pub const SILENT: Self = Self {
+        start: None,
+        success: None,
+        failure: None,
+    };
+}
+
+impl Default for LogLevels {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+static LOG_LEVELS: RwLock<LogLevels> = RwLock::new(LogLevels::DEFAULT);
+
+/// Set the log levels of all commands that do not set their own with `Sheller::log_levels`.
+///
+/// # Examples
+/// ```
+/// use sheller::{set_log_levels, LogLevels};
+///
+/// set_log_levels(LogLevels::SILENT);
+/// # set_log_levels(LogLevels::DEFAULT);
+/// ```
+pub fn set_log_levels(levels: LogLevels) {
+    *LOG_LEVELS.write().unwrap_or_else(PoisonError::into_inner) = levels;
+}
+
+/// Returns the log levels set by `set_log_levels`.
+#[must_use]
+pub fn log_levels() -> LogLevels {
+    *LOG_LEVELS.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Log an event at an `Option<Level>` chosen at runtime, and nothing if it is `None`.
+///
+/// The fields and the message are written as for the `tracing` macros, with the message last.
+macro_rules! event {
+    ($level:expr, $($rest:tt)+) => {
+        if let Some(level) = ::std::convert::Into::<Option<$crate::Level>>::into($level) {
+            match level {
+                $crate::Level::Trace => ::tracing::trace!($($rest)+),
+                $crate::Level::Debug => ::tracing::debug!($($rest)+),
+                $crate::Level::Info => ::tracing::info!($($rest)+),
+                $crate::Level::Warn => ::tracing::warn!($($rest)+),
+                $crate::Level::Error => ::tracing::error!($($rest)+),
+            }
+        }
+    };
+}
+
+pub(crate) use event;
+
+/// The target of the events that carry the output of commands, to filter them apart from the events about commands.
+pub(crate) const OUTPUT_TARGET: &str = "sheller::output";
+
+/// Returns a new identifier that tells the runs of commands apart in logs.
+fn next_run_id() -> u64 {
+    static NEXT_RUN_ID: AtomicU64 = AtomicU64::new(1);
+    NEXT_RUN_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Log a line that a command wrote to `stream` at `level`.
+pub(crate) fn output_line(level: Level, stream: &'static str, line: &str) {
+    match level {
+        Level::Trace => tracing::trace!(target: OUTPUT_TARGET, stream, "{line}"),
+        Level::Debug => tracing::debug!(target: OUTPUT_TARGET, stream, "{line}"),
+        Level::Info => tracing::info!(target: OUTPUT_TARGET, stream, "{line}"),
+        Level::Warn => tracing::warn!(target: OUTPUT_TARGET, stream, "{line}"),
+        Level::Error => tracing::error!(target: OUTPUT_TARGET, stream, "{line}"),
+    }
+}
+
+/// Create the span that a run of a command is wrapped in.
+///
+/// `pid`, `duration_ms`, and `exit_code` or `signal` are empty until they are recorded to the current span.
+pub(crate) fn run_span(command: &dyn std::fmt::Debug, program: &OsStr, cwd: Option<&Path>) -> Span {
+    let cwd = cwd
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::current_dir().ok());
+    info_span!(
+        "run",
+        run_id = next_run_id(),
+        command = ?command,
+        program = %program.to_string_lossy(),
+        cwd = cwd.as_deref().map(|cwd| field::display(cwd.display())),
+        pid = field::Empty,
+        duration_ms = field::Empty,
+        exit_code = field::Empty,
+        signal = field::Empty,
+    )
+}
+
+pub(crate) fn record_pid(pid: u32) {
+    Span::current().record("pid", pid);
+}
+
+pub(crate) fn record_duration(duration: Duration) {
+    let duration_ms = u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+    Span::current().record("duration_ms", duration_ms);
+}
+
+pub(crate) fn record_exit_code(exit_code: i32) {
+    Span::current().record("exit_code", exit_code);
+}
+
+pub(crate) fn record_signal(signal: Signal) {
+    Span::current().record("signal", field::display(signal));
+}
@@ -66,6 +66,158 @@ macro_rules! try_run {
     }};
 }
 
+/// Macro to run a shell script and return its stdout as a `String`.\
+/// The trailing newline is stripped and platform-specific line endings are normalized to `\n`.\
+/// It will panic if the script fails.
+///
+/// # Examples
+///
+/// ```
+/// use sheller::read;
+///
+/// assert_eq!(read!("echo hello"), "hello");
+/// assert_eq!(read!("{}", String::from("echo hello")), "hello");
+/// assert_eq!(read!("echo {}", "hello"), "hello");
+/// assert_eq!(read!("echo {hello}", hello = "hello"), "hello");
+/// assert_eq!(read!("echo {} {world}", "hello", world = "world"), "hello world");
+#[macro_export]
+macro_rules! read {
+    ($script_fmt:expr) => {
+        $crate::new!($script_fmt).read()
+    };
+    ($script_fmt:expr, $($arg:tt)*) => {
+        $crate::new!($script_fmt, $($arg)*).read()
+    };
+}
+
+/// Macro to try to run a shell script and return its stdout as a `String`.\
+/// It will return a `Result`. If the script fails, it will return an `Err` with the captured stderr. Otherwise, it will return an `Ok` with stdout.\
+/// The trailing newline is stripped and platform-specific line endings are normalized to `\n`.\
+///
+/// # Examples
+///
+/// ```
+/// use sheller::try_read;
+///
+/// assert_eq!(try_read!("echo hello").unwrap(), "hello");
+/// assert_eq!(try_read!("{}", String::from("echo hello")).unwrap(), "hello");
+/// assert_eq!(try_read!("echo {}", "hello").unwrap(), "hello");
+/// assert_eq!(try_read!("echo {hello}", hello = "hello").unwrap(), "hello");
+/// assert_eq!(try_read!("echo {} {world}", "hello", world = "world").unwrap(), "hello world");
+#[macro_export]
+macro_rules! try_read {
+    ($script_fmt:expr) => {{
+        let result = $crate::new!($script_fmt).try_read();
+        result
+    }};
+    ($script_fmt:expr, $($arg:tt)*) => {{
+        let result = $crate::new!($script_fmt, $($arg)*).try_read();
+        result
+    }};
+}
+
+/// Macro to create a new `Sheller` instance with every interpolated value quoted for the shell.\
+/// The literal parts of the script are kept as they are, and each positional or named argument is quoted with `Sheller::quote`.\
+/// Identifiers captured inline such as `{path}` can not be quoted, so they are rejected at compile time. Pass them as named arguments such as `path = path` instead.
+///
+/// # Examples
+///
+/// ```
+/// use sheller::sh;
+///
+/// let path = "my file; rm -rf /";
+/// let sheller = sh!("echo {}", path);
+/// # #[cfg(unix)]
+/// assert_eq!(sheller.read(), "my file; rm -rf /");
+/// sh!("echo {greeting} {}", "world", greeting = "hello").run();
+/// sh!("echo {path}", path = path).run();
+/// ```
+///
+/// ```compile_fail
+/// use sheller::sh;
+///
+/// let path = "my file; rm -rf /";
+/// sh!("echo {path}").run();
+/// ```
+#[macro_export]
+macro_rules! sh {
+    ($script_fmt:expr $(,)?) => {
+        $crate::Sheller::new($crate::__sh_format!(quoter ($script_fmt) [] []))
+    };
+    ($script_fmt:expr, $($arg:tt)*) => {{
+        let quoter = $crate::Sheller::default();
+        $crate::Sheller::new($crate::__sh_format!(quoter ($script_fmt) [] [] $($arg)*))
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sh_format {
+    ($quoter:ident ($script_fmt:expr) [$($out:tt)*] [$($name:expr),*]) => {{
+        const _: () = assert!(
+            $crate::__sh_names_are_passed($script_fmt, &[$($name),*]),
+            "`sh!` can not quote identifiers captured inline, pass them as named arguments such as `path = path`"
+        );
+        format!($script_fmt $($out)*)
+    }};
+    ($quoter:ident ($script_fmt:expr) [$($out:tt)*] [$($names:expr),*] $name:ident = $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__sh_format!($quoter ($script_fmt) [$($out)*, $name = $quoter.quote(&$value)] [$($names,)* stringify!($name)] $($($rest)*)?)
+    };
+    ($quoter:ident ($script_fmt:expr) [$($out:tt)*] [$($names:expr),*] $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__sh_format!($quoter ($script_fmt) [$($out)*, $quoter.quote(&$value)] [$($names),*] $($($rest)*)?)
+    };
+}
+
+/// Returns `true` if every argument that `script_fmt` names, such as `{path}`, is one of `names`.
+///
+/// `sh!` asserts it at compile time, because it can only quote the arguments that are passed to it.
+#[doc(hidden)]
+#[must_use]
+pub const fn __sh_names_are_passed(script_fmt: &str, names: &[&str]) -> bool {
+    let bytes = script_fmt.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'{' {
+            i += 1;
+            continue;
+        }
+        if i + 1 < bytes.len() && bytes[i + 1] == b'{' {
+            i += 2;
+            continue;
+        }
+        let start = i + 1;
+        let mut end = start;
+        while end < bytes.len() && bytes[end] != b'}' && bytes[end] != b':' {
+            end += 1;
+        }
+        let positional = end == start || bytes[start].is_ascii_digit();
+        if !positional && !contains(names, bytes, start, end) {
+            return false;
+        }
+        i = end;
+    }
+    true
+}
+
+/// Returns `true` if `bytes[start..end]` is one of `names`.
+const fn contains(names: &[&str], bytes: &[u8], start: usize, end: usize) -> bool {
+    let mut n = 0;
+    while n < names.len() {
+        let name = names[n].as_bytes();
+        if name.len() == end - start {
+            let mut k = 0;
+            while k < name.len() && name[k] == bytes[start + k] {
+                k += 1;
+            }
+            if k == name.len() {
+                return true;
+            }
+        }
+        n += 1;
+    }
+    false
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -199,4 +351,94 @@ mod tests {
         let name = "sheller";
         try_run!("echo {} {world}, {name}", "hello", world = "world").unwrap();
     }
+
+    #[test]
+    fn read_literal() {
+        assert_eq!(read!("echo hello"), "hello");
+    }
+
+    #[test]
+    fn read_string() {
+        let echo_hello = String::from("echo hello");
+        assert_eq!(read!("{}", echo_hello), "hello");
+    }
+
+    #[test]
+    fn read_format_positional_and_named_parameters() {
+        assert_eq!(
+            read!("echo {} {world}", "hello", world = "world"),
+            "hello world"
+        );
+    }
+
+    #[test]
+    fn read_format_all_parameters() {
+        let name = "sheller";
+        assert_eq!(
+            read!("echo {} {world}, {name}", "hello", world = "world"),
+            "hello world, sheller"
+        );
+    }
+
+    #[test]
+    fn read_multiple_lines() {
+        assert_eq!(read!("echo hello&& echo world"), "hello\nworld");
+    }
+
+    #[test]
+    fn try_read_literal() {
+        assert_eq!(try_read!("echo hello").unwrap(), "hello");
+    }
+
+    #[test]
+    fn try_read_format_positional_and_named_parameters() {
+        let output = try_read!("echo {} {world}", "hello", world = "world").unwrap();
+        assert_eq!(output, "hello world");
+    }
+
+    #[test]
+    fn try_read_failure_carries_stderr() {
+        let error = try_read!("echo oops 1>&2&& exit 1").unwrap_err();
+        let crate::Error::ExitCode { code, context } = error else {
+            panic!("expected an exit code error");
+        };
+        assert_eq!(code, 1);
+        assert_eq!(context.stderr_tail.as_deref(), Some("oops"));
+    }
+
+    #[test]
+    fn sh_literal() {
+        sh!("echo hello").run();
+    }
+
+    #[test]
+    fn sh_format_positional_and_named_parameters() {
+        let output = sh!("echo {} {world}", "hello", world = "world").read();
+        assert_eq!(output, "hello world");
+    }
+
+    #[test]
+    fn sh_format_trailing_comma() {
+        assert_eq!(sh!("echo {}", "hello",).read(), "hello");
+    }
+
+    #[test]
+    fn sh_names_are_passed() {
+        use crate::__sh_names_are_passed;
+        assert!(__sh_names_are_passed("echo {} {0} {{path}} {:>8}", &[]));
+        assert!(__sh_names_are_passed(
+            "echo {path:?} {name}",
+            &["name", "path"]
+        ));
+        assert!(!__sh_names_are_passed("echo {path}", &[]));
+        assert!(!__sh_names_are_passed("echo {path} {}", &["pat"]));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn sh_quotes_injection() {
+        let value = "it's $(echo injected); `echo injected` \\ \"";
+        assert_eq!(sh!("printf '%s' {}", value).read(), value);
+        assert_eq!(sh!("printf '%s' {value}", value = value).read(), value);
+    }
 }
@@ -0,0 +1,274 @@
+use crate::{exec, logging::event, Output, Result, Sheller};
+use std::{
+    process::{Child, Command, ExitStatus, Stdio},
+    time::{Duration, Instant},
+};
+use tracing::Span;
+
+/// Commands connected with OS pipes, like `a | b | c` in a shell.
+///
+/// The stdout of each stage is connected to the stdin of the next one, and all stages run concurrently.
+/// The stdin of the first stage, and the stdout of the last one unless it is captured, are left as they are configured.
+/// Each stage is logged and classified like `try_run`, with the exit codes accepted by its `Sheller`.
+///
+/// The pipeline fails like `set -o pipefail`: with the error of the rightmost stage that failed.
+/// A stage that stops reading early can make the stage before it fail with `SIGPIPE`, as it would in a shell with `pipefail`.
+/// If a stage fails to spawn, the stage after it reads from an empty stdin.
+///
+/// # Examples
+/// ```
+/// use sheller::{Pipeline, Sheller};
+///
+/// let stdout = Pipeline::new()
+///     .sheller(Sheller::new("echo hello"))
+///     .sheller(Sheller::new("sort"))
+///     .read();
+/// assert_eq!(stdout, "hello");
+/// ```
+#[derive(Debug, Default)]
+pub struct Pipeline {
+    stages: Vec<(Command, exec::Options)>,
+}
+
+impl Pipeline {
+    /// Create an empty `Pipeline`.
+    ///
+    /// An empty pipeline succeeds without running anything.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `command` as the next stage.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::Pipeline;
+    /// use std::process::Command;
+    ///
+    /// # #[cfg(unix)]
+    /// # {
+    /// let mut echo = Command::new("echo");
+    /// echo.arg("hello");
+    /// let stdout = Pipeline::new()
+    ///     .command(echo)
+    ///     .command(Command::new("cat"))
+    ///     .read();
+    /// assert_eq!(stdout, "hello");
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn command(mut self, command: Command) -> Self {
+        self.stages.push((command, exec::Options::default()));
+        self
+    }
+
+    /// Append the command of `sheller` as the next stage, with its options.
+    ///
+    /// Line callbacks and logged output of stdout only apply to the last stage, because the stdout of the other stages goes to the next one.
+    #[must_use]
+    pub fn sheller(mut self, sheller: Sheller) -> Self {
+        self.stages.push(sheller.into_parts());
+        self
+    }
+
+    /// Run the pipeline and panic if any stage failed to run.
+    ///
+    /// # Panics
+    /// Panics if any stage failed to run.
+    pub fn run(self) {
+        self.try_run().unwrap();
+    }
+
+    /// Run the pipeline and return a `Result`.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::{Error, Pipeline, Sheller};
+    ///
+    /// let result = Pipeline::new()
+    ///     .sheller(Sheller::new("exit 3"))
+    ///     .sheller(Sheller::new("echo hello"))
+    ///     .try_run();
+    /// assert!(matches!(result, Err(Error::ExitCode { code: 3, .. })));
+    /// ```
+    ///
+    /// # Errors
+    /// Returns the error of the rightmost stage that failed to run.
+    pub fn try_run(self) -> Result<()> {
+        pipefail(self.try_run_stages())
+    }
+
+    /// Run the pipeline and return the result of every stage, in order.
+    ///
+    /// The result of a stage is its accepted exit code, or its error as `try_run_code` returns it.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::{Pipeline, Sheller};
+    ///
+    /// let results = Pipeline::new()
+    ///     .sheller(Sheller::new("exit 3"))
+    ///     .sheller(Sheller::new("echo hello"))
+    ///     .try_run_stages();
+    /// assert!(results[0].is_err());
+    /// assert_eq!(results[1].as_ref().unwrap(), &0);
+    /// ```
+    #[must_use]
+    pub fn try_run_stages(self) -> Vec<Result<i32>> {
+        self.start(false).into_iter().map(Stage::check).collect()
+    }
+
+    /// Run the pipeline, capture the stdout and stderr of the last stage, and return an `Output`.
+    ///
+    /// The stderr of the other stages is left as it is configured.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::{Pipeline, Sheller};
+    ///
+    /// let output = Pipeline::new()
+    ///     .sheller(Sheller::new("echo hello"))
+    ///     .sheller(Sheller::new("sort"))
+    ///     .try_output()
+    ///     .unwrap();
+    /// assert_eq!(output.stdout_string().unwrap(), "hello");
+    /// ```
+    ///
+    /// # Errors
+    /// Returns the error of the rightmost stage that failed to run.
+    pub fn try_output(self) -> Result<Output> {
+        let mut stages = self.start(true);
+        let Some(last) = stages.pop() else {
+            return Ok(Output {
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+                status: ExitStatus::default(),
+                duration: Duration::ZERO,
+            });
+        };
+        let results: Vec<_> = stages.into_iter().map(Stage::check).collect();
+        let output = last.finish(exec::finish_output)?;
+        pipefail(results)?;
+        Ok(output)
+    }
+
+    /// Run the pipeline and return the stdout of the last stage without the trailing newline.
+    ///
+    /// # Panics
+    /// Panics if any stage failed to run or stdout is not valid UTF-8.
+    #[must_use]
+    pub fn read(self) -> String {
+        self.try_read().unwrap()
+    }
+
+    /// Run the pipeline and return the stdout of the last stage without the trailing newline as a `Result`.
+    ///
+    /// # Errors
+    /// Returns an `Err` if any stage failed to run or stdout is not valid UTF-8.
+    pub fn try_read(self) -> Result<String> {
+        self.try_output()?.stdout_string()
+    }
+
+    /// Spawn every stage, connecting the stdout of each to the stdin of the next.
+    ///
+    /// The stdout and stderr of the last stage are captured if `capture` is `true`.
+    fn start(self, capture: bool) -> Vec<Stage> {
+        let last = self.stages.len().saturating_sub(1);
+        let mut stdin = None;
+        let mut stages = Vec::with_capacity(self.stages.len());
+        for (index, (mut command, options)) in self.stages.into_iter().enumerate() {
+            let span = exec::run_span(&command, &options);
+            let child = span.in_scope(|| {
+                event!(options.log_levels().start, command = ?options.redact(&command), "Running command.");
+                options.pipe_line_streams(&mut command);
+                if let Some(stdin) = stdin.take() {
+                    command.stdin(stdin);
+                }
+                if index < last {
+                    command.stdout(Stdio::piped());
+                } else if capture {
+                    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+                }
+                let start = Instant::now();
+                let piped_stdout = index < last || capture || options.pipes_stdout();
+                let mut child = exec::spawn(&mut command, &options, start, piped_stdout);
+                // Close the read end of the previous pipe in this process, so that the previous stage gets `SIGPIPE` if this one exits.
+                command.stdin(Stdio::null());
+                if index < last {
+                    let stdout = child.as_mut().ok().and_then(|child| child.stdout.take());
+                    stdin = Some(stdout.map_or_else(Stdio::null, Stdio::from));
+                }
+                child.map(|mut child| {
+                    let streams =
+                        exec::Streams::start(&mut child, &options, capture && index == last);
+                    (child, streams, start)
+                })
+            });
+            stages.push(Stage {
+                command,
+                options,
+                span,
+                child,
+            });
+        }
+        stages
+    }
+}
+
+/// A stage of a running pipeline.
+struct Stage {
+    command: Command,
+    options: exec::Options,
+    span: Span,
+    child: Result<(Child, exec::Streams, Instant)>,
+}
+
+impl Stage {
+    /// Wait for the stage to exit, and classify its exit status.
+    fn check(self) -> Result<i32> {
+        self.finish(|command, options, status, joined, duration| {
+            exec::check_status(command, status, duration, options)
+                .map_err(|e| exec::report_output(e, options, &joined))
+        })
+    }
+
+    /// Wait for the stage to exit, and pass its exit status and captured output to `classify`.
+    fn finish<T>(
+        self,
+        classify: impl FnOnce(
+            &dyn std::fmt::Debug,
+            &exec::Options,
+            ExitStatus,
+            exec::Joined,
+            Duration,
+        ) -> Result<T>,
+    ) -> Result<T> {
+        let Self {
+            command,
+            options,
+            span,
+            child,
+        } = self;
+        let _span = span.entered();
+        let (mut child, streams, start) = child?;
+        let status = match exec::wait(&command, &mut child, &options, start) {
+            Ok(status) => status,
+            Err(e) => return Err(streams.report(e, &options)),
+        };
+        let duration = start.elapsed();
+        let joined = streams
+            .join()
+            .map_err(|e| exec::wait_error(&command, &options, e, start))?;
+        classify(&command, &options, status, joined, duration)
+    }
+}
+
+/// Returns the error of the rightmost stage that failed.
+fn pipefail(results: Vec<Result<i32>>) -> Result<()> {
+    results
+        .into_iter()
+        .filter_map(Result::err)
+        .last()
+        .map_or(Ok(()), Err)
+}
@@ -0,0 +1,431 @@
+use std::path::Path;
+
+/// A shell family that Sheller knows how to talk to.
+///
+/// The family decides how interpolated values are quoted.
+/// Please see the `Shell::from_program` method for how it is detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Shell {
+    /// POSIX `sh`.
+    Sh,
+    /// GNU Bash.
+    Bash,
+    /// Z shell.
+    Zsh,
+    /// Debian Almquist shell.
+    Dash,
+    /// Korn shell and its variants such as `mksh` and `oksh`.
+    Ksh,
+    /// Friendly interactive shell.
+    Fish,
+    /// Windows `cmd.exe`.
+    Cmd,
+    /// PowerShell, both `pwsh` and Windows `powershell.exe`.
+    PowerShell,
+    /// Nushell.
+    Nu,
+    /// Xonsh, the Python-powered shell.
+    Xonsh,
+    /// Elvish.
+    Elvish,
+}
+
+impl Shell {
+    /// Detect the shell family from the file name of `program`.
+    ///
+    /// Returns `None` if the program is not a known shell.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Shell;
+    ///
+    /// assert_eq!(Shell::from_program("/bin/sh"), Some(Shell::Sh));
+    /// assert_eq!(Shell::from_program("/usr/local/bin/fish"), Some(Shell::Fish));
+    /// assert_eq!(Shell::from_program("cmd.exe"), Some(Shell::Cmd));
+    /// assert_eq!(Shell::from_program("/usr/bin/python3"), None);
+    /// ```
+    pub fn from_program<P>(program: P) -> Option<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let stem = program.as_ref().file_stem()?.to_str()?.to_ascii_lowercase();
+        match stem.as_str() {
+            "sh" => Some(Shell::Sh),
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "dash" => Some(Shell::Dash),
+            "ksh" | "mksh" | "oksh" | "pdksh" => Some(Shell::Ksh),
+            "fish" => Some(Shell::Fish),
+            "cmd" => Some(Shell::Cmd),
+            "pwsh" | "powershell" => Some(Shell::PowerShell),
+            "nu" => Some(Shell::Nu),
+            "xonsh" => Some(Shell::Xonsh),
+            "elvish" => Some(Shell::Elvish),
+            _ => None,
+        }
+    }
+
+    /// Returns the program name of this shell, which is looked up in `PATH`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Shell;
+    ///
+    /// assert_eq!(Shell::Bash.program(), "bash");
+    /// assert_eq!(Shell::Cmd.program(), "cmd.exe");
+    /// ```
+    #[must_use]
+    pub fn program(self) -> &'static str {
+        match self {
+            Shell::Sh => "sh",
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Dash => "dash",
+            Shell::Ksh => "ksh",
+            Shell::Fish => "fish",
+            Shell::Cmd => "cmd.exe",
+            Shell::PowerShell => "pwsh",
+            Shell::Nu => "nu",
+            Shell::Xonsh => "xonsh",
+            Shell::Elvish => "elvish",
+        }
+    }
+
+    /// Returns the flags that make this shell run the script passed as the next argument.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Shell;
+    ///
+    /// assert_eq!(Shell::Zsh.flags(), ["-c"]);
+    /// assert_eq!(Shell::Cmd.flags(), ["/D", "/S", "/C"]);
+    /// assert_eq!(Shell::PowerShell.flags(), ["-NoProfile", "-NonInteractive", "-Command"]);
+    /// ```
+    #[must_use]
+    pub fn flags(self) -> &'static [&'static str] {
+        match self {
+            Shell::Cmd => &["/D", "/S", "/C"],
+            Shell::PowerShell => &["-NoProfile", "-NonInteractive", "-Command"],
+            Shell::Sh
+            | Shell::Bash
+            | Shell::Zsh
+            | Shell::Dash
+            | Shell::Ksh
+            | Shell::Fish
+            | Shell::Nu
+            | Shell::Xonsh
+            | Shell::Elvish => &["-c"],
+        }
+    }
+
+    /// Returns `true` if this shell understands POSIX `sh` syntax.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Shell;
+    ///
+    /// assert!(Shell::Bash.is_posix());
+    /// assert!(!Shell::Fish.is_posix());
+    /// ```
+    #[must_use]
+    pub fn is_posix(self) -> bool {
+        matches!(
+            self,
+            Shell::Sh | Shell::Bash | Shell::Zsh | Shell::Dash | Shell::Ksh
+        )
+    }
+
+    /// Returns the lines that make a script of this shell exit on the first failing command and on unset variables.
+    ///
+    /// POSIX shells get `set -eu`, and shells that support it also get `set -o pipefail`.
+    /// Returns `None` for shells that have no equivalent, so nothing they would reject is ever emitted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Shell;
+    ///
+    /// assert_eq!(Shell::Sh.strict_preamble(), Some("set -eu"));
+    /// assert_eq!(Shell::Bash.strict_preamble(), Some("set -eu\nset -o pipefail"));
+    /// assert_eq!(Shell::Fish.strict_preamble(), None);
+    /// ```
+    #[must_use]
+    pub fn strict_preamble(self) -> Option<&'static str> {
+        match self {
+            Shell::Sh | Shell::Dash => Some("set -eu"),
+            Shell::Bash | Shell::Zsh | Shell::Ksh => Some("set -eu\nset -o pipefail"),
+            Shell::Fish
+            | Shell::Cmd
+            | Shell::PowerShell
+            | Shell::Nu
+            | Shell::Xonsh
+            | Shell::Elvish => None,
+        }
+    }
+
+    /// Quote `value` so that this shell reads it back as a single literal word.
+    ///
+    /// Values made only of characters that are safe for this shell are returned unchanged.
+    /// `a-z`, `A-Z`, `0-9`, `-`, `_`, `.` and `/` are safe for every shell.
+    /// POSIX shells and fish also take `,`, `:`, `@` and `+`, cmd takes `:` and `+`, and the others take `:`.
+    /// A PowerShell value that starts with `-` is always quoted, so that it is not read as a parameter name.
+    ///
+    /// ## POSIX shells
+    ///
+    /// `sh`, `bash`, `zsh`, `dash` and `ksh` use single quotes, and `'` is written as `'\''`.
+    ///
+    /// ## Fish
+    ///
+    /// Single quotes, with `\` and `'` escaped by a backslash.
+    ///
+    /// ## Xonsh
+    ///
+    /// A Python string literal in single quotes, with `\` and `'` escaped by a backslash.
+    /// A Python string literal can not contain a raw line break, so newline and carriage return are written as `\n` and `\r`.
+    ///
+    /// ## PowerShell
+    ///
+    /// Single quotes, and `'` is written as `''`.
+    /// PowerShell also reads the typographic quotes `‘`, `’`, `‚` and `‛` as `'`, so they are doubled the same way.
+    ///
+    /// ## Elvish
+    ///
+    /// Single quotes, and `'` is written as `''`.
+    ///
+    /// ## Nu
+    ///
+    /// Single quotes, which have no escapes in Nushell.
+    /// If `value` contains `'`, a raw string such as `r#'...'#` is used instead.
+    ///
+    /// ## Cmd
+    ///
+    /// Double quotes, and `"` is written as `""`.
+    /// `cmd.exe` has no way to escape `%` inside quotes, so environment variable expansion can not be prevented.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sheller::Shell;
+    ///
+    /// assert_eq!(Shell::Sh.quote("hello"), "hello");
+    /// assert_eq!(Shell::Sh.quote("it's $(rm -rf /)"), r"'it'\''s $(rm -rf /)'");
+    /// assert_eq!(Shell::Fish.quote(r"it's \o/"), r"'it\'s \\o/'");
+    /// assert_eq!(Shell::Cmd.quote(r#"say "hi""#), r#""say ""hi""""#);
+    /// ```
+    #[must_use]
+    pub fn quote(self, value: &str) -> String {
+        if self.is_safe(value) {
+            return value.to_string();
+        }
+        match self {
+            Shell::Sh | Shell::Bash | Shell::Zsh | Shell::Dash | Shell::Ksh => {
+                format!("'{}'", value.replace('\'', r"'\''"))
+            }
+            Shell::Fish => {
+                format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+            }
+            Shell::Xonsh => format!(
+                "'{}'",
+                value
+                    .replace('\\', r"\\")
+                    .replace('\'', r"\'")
+                    .replace('\n', r"\n")
+                    .replace('\r', r"\r")
+            ),
+            Shell::PowerShell => {
+                let mut quoted = String::from("'");
+                for c in value.chars() {
+                    if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+                        quoted.push(c);
+                    }
+                    quoted.push(c);
+                }
+                quoted.push('\'');
+                quoted
+            }
+            Shell::Elvish => format!("'{}'", value.replace('\'', "''")),
+            Shell::Nu => quote_nu(value),
+            Shell::Cmd => format!("\"{}\"", value.replace('"', "\"\"")),
+        }
+    }
+
+    /// Returns `true` if `value` means the same to this shell without quotes.
+    fn is_safe(self, value: &str) -> bool {
+        if value.is_empty() || (self == Shell::PowerShell && value.starts_with('-')) {
+            return false;
+        }
+        value.chars().all(|c| {
+            c.is_ascii_alphanumeric()
+                || matches!(c, '-' | '_' | '.' | '/')
+                || match self {
+                    Shell::Sh
+                    | Shell::Bash
+                    | Shell::Zsh
+                    | Shell::Dash
+                    | Shell::Ksh
+                    | Shell::Fish => matches!(c, ',' | ':' | '@' | '+'),
+                    Shell::Cmd => matches!(c, ':' | '+'),
+                    Shell::PowerShell | Shell::Nu | Shell::Xonsh | Shell::Elvish => c == ':',
+                }
+        })
+    }
+}
+
+fn quote_nu(value: &str) -> String {
+    if !value.contains('\'') {
+        return format!("'{value}'");
+    }
+    let mut hashes = String::from("#");
+    while value.contains(&format!("'{hashes}")) {
+        hashes.push('#');
+    }
+    format!("r{hashes}'{value}'{hashes}")
+}
+
+/// How to handle the shell program from the `SHELL` or `COMSPEC` environment variable.
+///
+/// Set it with the `Sheller::shell_policy` method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ShellPolicy {
+    /// Use the program with the flags of its detected shell family, such as `-NoProfile -NonInteractive -Command` for PowerShell.
+    ///
+    /// If the shell family is unknown, fall back to the default program with a warning.
+    #[default]
+    Adapt,
+    /// Use the program only if it understands the syntax of the default program, which is POSIX `sh` on Unix and `cmd.exe` on Windows.
+    ///
+    /// Otherwise, such as for fish, Nushell or PowerShell on Unix, fall back to the default program with a warning.
+    Fallback,
+    /// Use the program as it is with the default flags, whatever it is.
+    Trust,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Shell, ShellPolicy};
+    use crate::Sheller;
+
+    fn resolve_env(env_program: &str, policy: ShellPolicy) -> (String, Vec<String>) {
+        let mut sheller = Sheller::new("echo hello").shell_policy(policy);
+        sheller.env_program = Some(env_program.to_string());
+        let invocation = sheller.resolve();
+        (invocation.program, invocation.args)
+    }
+
+    #[test]
+    fn from_program_windows_extension() {
+        assert_eq!(Shell::from_program("pwsh.exe"), Some(Shell::PowerShell));
+        assert_eq!(Shell::from_program("BASH.EXE"), Some(Shell::Bash));
+    }
+
+    #[test]
+    fn quote_empty() {
+        assert_eq!(Shell::Bash.quote(""), "''");
+        assert_eq!(Shell::Cmd.quote(""), "\"\"");
+    }
+
+    #[test]
+    fn quote_posix_special_characters() {
+        assert_eq!(Shell::Zsh.quote("a b"), "'a b'");
+        assert_eq!(Shell::Dash.quote("$HOME"), "'$HOME'");
+        assert_eq!(Shell::Bash.quote("a;b"), "'a;b'");
+        assert_eq!(Shell::Sh.quote("~"), "'~'");
+        assert_eq!(Shell::Sh.quote("a=b"), "'a=b'");
+    }
+
+    #[test]
+    fn quote_nu() {
+        assert_eq!(Shell::Nu.quote("a b"), "'a b'");
+        assert_eq!(Shell::Nu.quote("it's"), "r#'it's'#");
+        assert_eq!(Shell::Nu.quote("it'#s"), "r##'it'#s'##");
+    }
+
+    #[test]
+    fn quote_powershell() {
+        assert_eq!(Shell::PowerShell.quote("it's"), "'it''s'");
+        assert_eq!(Shell::PowerShell.quote("a,b"), "'a,b'");
+        assert_eq!(Shell::PowerShell.quote("@args"), "'@args'");
+        assert_eq!(Shell::PowerShell.quote("-Force"), "'-Force'");
+        assert_eq!(Shell::PowerShell.quote("C:/a-b"), "C:/a-b");
+    }
+
+    #[test]
+    fn quote_powershell_typographic_quotes() {
+        assert_eq!(
+            Shell::PowerShell.quote("it\u{2019}s"),
+            "'it\u{2019}\u{2019}s'"
+        );
+        assert_eq!(
+            Shell::PowerShell.quote("\u{2018}a\u{201A}b\u{201B}"),
+            "'\u{2018}\u{2018}a\u{201A}\u{201A}b\u{201B}\u{201B}'"
+        );
+        assert_eq!(Shell::Elvish.quote("it\u{2019}s"), "'it\u{2019}s'");
+    }
+
+    #[test]
+    fn quote_xonsh() {
+        assert_eq!(Shell::Xonsh.quote(r"it's \o/"), r"'it\'s \\o/'");
+        assert_eq!(Shell::Xonsh.quote("a\nb\r\n"), r"'a\nb\r\n'");
+        assert_eq!(Shell::Fish.quote("a\nb"), "'a\nb'");
+    }
+
+    #[test]
+    fn quote_cmd() {
+        assert_eq!(Shell::Cmd.quote("a,b"), "\"a,b\"");
+        assert_eq!(Shell::Cmd.quote("a@b"), "\"a@b\"");
+        assert_eq!(Shell::Cmd.quote("C:/a+b"), "C:/a+b");
+    }
+
+    #[test]
+    fn quote_posix_safe_characters() {
+        assert_eq!(Shell::Sh.quote("user@host:a,b+c"), "user@host:a,b+c");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn policy_adapt() {
+        let (program, args) = resolve_env("/usr/bin/pwsh", ShellPolicy::Adapt);
+        assert_eq!(program, "/usr/bin/pwsh");
+        assert_eq!(args, ["-NoProfile", "-NonInteractive", "-Command"]);
+        let (program, args) = resolve_env("/usr/bin/fish", ShellPolicy::Adapt);
+        assert_eq!(program, "/usr/bin/fish");
+        assert_eq!(args, ["-c"]);
+        let (program, args) = resolve_env("/usr/bin/unknown", ShellPolicy::Adapt);
+        assert_eq!(program, "/bin/sh");
+        assert_eq!(args, ["-c"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn policy_fallback() {
+        let (program, _) = resolve_env("/bin/zsh", ShellPolicy::Fallback);
+        assert_eq!(program, "/bin/zsh");
+        let (program, args) = resolve_env("/usr/bin/fish", ShellPolicy::Fallback);
+        assert_eq!(program, "/bin/sh");
+        assert_eq!(args, ["-c"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn policy_trust() {
+        let (program, args) = resolve_env("/usr/bin/pwsh", ShellPolicy::Trust);
+        assert_eq!(program, "/usr/bin/pwsh");
+        assert_eq!(args, ["-c"]);
+    }
+
+    #[test]
+    fn explicit_program_ignores_policy() {
+        let mut sheller = Sheller::new("echo hello")
+            .program("/usr/bin/nu")
+            .shell_policy(ShellPolicy::Fallback);
+        sheller.env_program = Some("/usr/bin/fish".to_string());
+        let invocation = sheller.resolve();
+        assert_eq!(invocation.program, "/usr/bin/nu");
+        assert_eq!(invocation.shell, Shell::Nu);
+    }
+}
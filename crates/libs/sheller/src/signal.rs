@@ -0,0 +1,151 @@
+/// A signal that terminated a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Signal {
+    number: i32,
+    core_dumped: bool,
+}
+
+impl Signal {
+    pub(crate) fn new(number: i32, core_dumped: bool) -> Self {
+        Self {
+            number,
+            core_dumped,
+        }
+    }
+
+    /// Returns the signal number.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::{Error, Sheller};
+    ///
+    /// # #[cfg(unix)]
+    /// # {
+    /// let error = Sheller::new("kill -KILL $$").try_run().unwrap_err();
+    /// let Error::Signal { signal, .. } = error else {
+    ///     panic!("expected a signal error");
+    /// };
+    /// assert_eq!(signal.number(), libc::SIGKILL);
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn number(&self) -> i32 {
+        self.number
+    }
+
+    /// Returns the name of the signal such as `SIGKILL`, or `None` if it is not a well-known signal.
+    #[must_use]
+    pub fn name(&self) -> Option<&'static str> {
+        name(self.number)
+    }
+
+    /// Returns `true` if the process dumped core when it was terminated.
+    #[must_use]
+    pub fn core_dumped(&self) -> bool {
+        self.core_dumped
+    }
+
+    /// Returns `true` if the process was likely killed by the out-of-memory killer.
+    ///
+    /// The OOM killer sends `SIGKILL`, so this is a guess: any `SIGKILL` from outside, such as `kill -9`, looks the same.
+    /// Commands killed by sheller itself after a timeout fail with `Error::Timeout` instead.
+    #[must_use]
+    pub fn is_oom_kill_likely(&self) -> bool {
+        #[cfg(unix)]
+        {
+            self.number == libc::SIGKILL
+        }
+        #[cfg(windows)]
+        {
+            false
+        }
+    }
+}
+
+impl std::fmt::Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{name} ({})", self.number)?,
+            None => write!(f, "signal {}", self.number)?,
+        }
+        if self.core_dumped {
+            write!(f, ", core dumped")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn name(number: i32) -> Option<&'static str> {
+    let name = match number {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGUSR1 => "SIGUSR1",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGUSR2 => "SIGUSR2",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGALRM => "SIGALRM",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGCHLD => "SIGCHLD",
+        libc::SIGCONT => "SIGCONT",
+        libc::SIGSTOP => "SIGSTOP",
+        libc::SIGTSTP => "SIGTSTP",
+        libc::SIGTTIN => "SIGTTIN",
+        libc::SIGTTOU => "SIGTTOU",
+        libc::SIGURG => "SIGURG",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        libc::SIGVTALRM => "SIGVTALRM",
+        libc::SIGPROF => "SIGPROF",
+        libc::SIGWINCH => "SIGWINCH",
+        libc::SIGIO => "SIGIO",
+        libc::SIGSYS => "SIGSYS",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(windows)]
+fn name(_: i32) -> Option<&'static str> {
+    None
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_named() {
+        assert_eq!(
+            Signal::new(libc::SIGKILL, false).to_string(),
+            format!("SIGKILL ({})", libc::SIGKILL)
+        );
+    }
+
+    #[test]
+    fn display_core_dumped() {
+        assert_eq!(
+            Signal::new(libc::SIGSEGV, true).to_string(),
+            format!("SIGSEGV ({}), core dumped", libc::SIGSEGV)
+        );
+    }
+
+    #[test]
+    fn display_unnamed() {
+        assert_eq!(Signal::new(1000, false).to_string(), "signal 1000");
+    }
+
+    #[test]
+    fn oom_kill_likely() {
+        assert!(Signal::new(libc::SIGKILL, false).is_oom_kill_likely());
+        assert!(!Signal::new(libc::SIGTERM, false).is_oom_kill_likely());
+    }
+}
@@ -0,0 +1,267 @@
+//! Async execution on the `tokio` runtime.
+//!
+//! Enabled by the `tokio` feature.
+
+use crate::{exec, Output, Result, Sheller};
+use ::tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    process::{Child, ChildStderr, ChildStdout, Command},
+    time,
+};
+use std::{
+    ffi::OsStr,
+    future::Future,
+    io,
+    path::Path,
+    process::{ExitStatus, Stdio},
+    time::Duration,
+};
+
+impl Sheller {
+    /// Run the shell command asynchronously and panic if the command failed to run.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// Sheller::new("echo hello").run_async().await;
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the command failed to run.
+    pub async fn run_async(self) {
+        self.try_run_async().await.unwrap();
+    }
+
+    /// Run the shell command asynchronously and return a `Result`.
+    ///
+    /// The timeout, the process group option and the output options such as tee, line callbacks and logging output apply as they do to `try_run`.
+    /// If the returned future is dropped before it completes, the command is killed, together with its whole process group if it was spawned in one.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// Sheller::new("echo hello").try_run_async().await.unwrap();
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run.
+    pub async fn try_run_async(self) -> Result<()> {
+        let (mut command, options) = self.into_tokio_parts();
+        exec::run_async::<Tokio>(&mut command, &options).await?;
+        Ok(())
+    }
+
+    /// Run the shell command asynchronously, capture its stdout and stderr, and return an `Output`.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::Sheller;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let output = Sheller::new("echo hello").output_async().await.unwrap();
+    /// assert_eq!(output.stdout_string().unwrap(), "hello");
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run.
+    pub async fn output_async(self) -> Result<Output> {
+        let (mut command, options) = self.into_tokio_parts();
+        exec::output_async::<Tokio>(&mut command, &options).await
+    }
+
+    fn into_tokio_parts(self) -> (Command, exec::Options) {
+        let (mut command, options) = self.into_parts();
+        exec::prepare(&mut command, &options);
+        let mut command = Command::from(command);
+        command.kill_on_drop(true);
+        (command, options)
+    }
+}
+
+/// Extension of `tokio::process::Command` with the same behavior as `CommandExt`.
+pub trait AsyncCommandExt {
+    /// Run the command asynchronously and panic if the command failed to run.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::AsyncCommandExt;
+    /// use tokio::process::Command;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #[cfg(windows)]
+    /// Command::new("cmd.exe")
+    ///     .args(["/D", "/S", "/C", "echo hello"])
+    ///     .run_async()
+    ///     .await;
+    ///
+    /// #[cfg(unix)]
+    /// Command::new("echo").arg("hello").run_async().await;
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the command failed to run.
+    fn run_async(&mut self) -> impl Future<Output = ()> + Send;
+
+    /// Run the command asynchronously and return a `Result`.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::AsyncCommandExt;
+    /// use tokio::process::Command;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #[cfg(windows)]
+    /// Command::new("cmd.exe")
+    ///     .args(["/D", "/S", "/C", "echo hello"])
+    ///     .try_run_async()
+    ///     .await
+    ///     .unwrap();
+    ///
+    /// #[cfg(unix)]
+    /// Command::new("echo")
+    ///     .arg("hello")
+    ///     .try_run_async()
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run.
+    fn try_run_async(&mut self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Run the command asynchronously, capture its stdout and stderr, and return an `Output`.
+    ///
+    /// stdout and stderr are always piped, and stdin is left as it is configured.
+    ///
+    /// # Examples
+    /// ```
+    /// use sheller::AsyncCommandExt;
+    /// use tokio::process::Command;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #[cfg(windows)]
+    /// let output = Command::new("cmd.exe")
+    ///     .args(["/D", "/S", "/C", "echo hello"])
+    ///     .output_async()
+    ///     .await
+    ///     .unwrap();
+    ///
+    /// #[cfg(unix)]
+    /// let output = Command::new("echo")
+    ///     .arg("hello")
+    ///     .output_async()
+    ///     .await
+    ///     .unwrap();
+    ///
+    /// assert_eq!(output.stdout_string().unwrap(), "hello");
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an `Err` if the command failed to run.
+    fn output_async(&mut self) -> impl Future<Output = Result<Output>> + Send;
+}
+
+impl AsyncCommandExt for Command {
+    async fn run_async(&mut self) {
+        self.try_run_async().await.unwrap();
+    }
+
+    async fn try_run_async(&mut self) -> Result<()> {
+        exec::run_async::<Tokio>(self, &exec::Options::default()).await?;
+        Ok(())
+    }
+
+    async fn output_async(&mut self) -> Result<Output> {
+        exec::output_async::<Tokio>(self, &exec::Options::default()).await
+    }
+}
+
+/// The `tokio` runtime, which async execution runs on through its primitives.
+struct Tokio;
+
+impl exec::Runtime for Tokio {
+    type Command = Command;
+    type Child = Child;
+    type Stdout = ChildStdout;
+    type Stderr = ChildStderr;
+
+    fn program(command: &Command) -> &OsStr {
+        command.as_std().get_program()
+    }
+
+    fn current_dir(command: &Command) -> Option<&Path> {
+        command.as_std().get_current_dir()
+    }
+
+    fn spawn(command: &mut Command) -> io::Result<Child> {
+        command.spawn()
+    }
+
+    fn id(child: &Child) -> Option<u32> {
+        child.id()
+    }
+
+    fn take_stdout(child: &mut Child) -> Option<ChildStdout> {
+        child.stdout.take()
+    }
+
+    fn take_stderr(child: &mut Child) -> Option<ChildStderr> {
+        child.stderr.take()
+    }
+
+    fn read_stdout(stdout: ChildStdout) -> impl Future<Output = io::Result<Vec<u8>>> + Send {
+        read_to_end(stdout)
+    }
+
+    fn read_stderr(stderr: ChildStderr) -> impl Future<Output = io::Result<Vec<u8>>> + Send {
+        read_to_end(stderr)
+    }
+
+    fn wait(child: &mut Child) -> impl Future<Output = io::Result<ExitStatus>> + Send {
+        child.wait()
+    }
+
+    fn start_kill(child: &mut Child) -> io::Result<()> {
+        child.start_kill()
+    }
+
+    fn sleep(duration: Duration) -> impl Future<Output = ()> + Send {
+        time::sleep(duration)
+    }
+}
+
+impl exec::SetStdio for Command {
+    fn set_stdin(&mut self, stdio: Stdio) {
+        self.stdin(stdio);
+    }
+
+    fn set_stdout(&mut self, stdio: Stdio) {
+        self.stdout(stdio);
+    }
+
+    fn set_stderr(&mut self, stdio: Stdio) {
+        self.stderr(stdio);
+    }
+}
+
+async fn read_to_end(mut reader: impl AsyncRead + Unpin) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    reader.read_to_end(&mut buffer).await?;
+    Ok(buffer)
+}
@@ -1,7 +1,12 @@
 #[cfg(test)]
 mod tests {
-    use sheller::{new, CommandExt};
-    use std::{ffi::OsStr, path::Path};
+    use sheller::{new, CommandExt, DropPolicy, Error, Pipeline, Shell, Sheller};
+    use std::{
+        ffi::OsStr,
+        path::Path,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
 
     #[test]
     #[cfg(windows)]
@@ -11,7 +16,16 @@ mod tests {
         let file_name = Path::new(program).file_name().unwrap().to_str().unwrap();
         assert_eq!(file_name, "cmd.exe");
         let args = command.get_args().collect::<Vec<&OsStr>>();
-        assert_eq!(args, vec!["/D", "/S", "/C", "echo hello"]);
+        assert_eq!(args, vec!["/D", "/S", "/C", "\"echo hello\""]);
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn quote_cmd_passes_quotes_verbatim() {
+        let value = r#""a b" c"#;
+        let expected = r#""""a b"" c""#;
+        assert_eq!(sheller::sh!("echo {}", value).read(), expected);
+        assert_eq!(Sheller::new("echo").arg_quoted(value).read(), expected);
     }
 
     #[test]
@@ -89,4 +103,1253 @@ mod tests {
         let eol = if cfg!(windows) { "\r\n" } else { "\n" };
         assert_eq!(output.stdout, format!("hello{}", eol).as_bytes());
     }
+
+    #[test]
+    fn try_output() {
+        let output = new!("echo hello").try_output().unwrap();
+        assert!(output.status.success());
+        let eol = if cfg!(windows) { "\r\n" } else { "\n" };
+        assert_eq!(output.stdout, format!("hello{}", eol).as_bytes());
+    }
+
+    #[test]
+    fn try_output_non_zero_exit_code() {
+        let error = new!("exit 3").try_output().unwrap_err();
+        assert!(matches!(error, sheller::Error::ExitCode { code: 3, .. }));
+    }
+
+    #[test]
+    fn read() {
+        assert_eq!(new!("echo hello").read(), "hello");
+    }
+
+    #[test]
+    fn try_read() {
+        assert_eq!(new!("echo hello").try_read().unwrap(), "hello");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn read_keeps_leading_whitespace() {
+        assert_eq!(new!("printf '  indented\\n\\n'").read(), "  indented");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn positional_args() {
+        let command = Sheller::new("echo \"$1\"").positional(["a b"]).build();
+        let program = command.get_program().to_owned();
+        let args = command.get_args().collect::<Vec<&OsStr>>();
+        assert_eq!(
+            args,
+            vec![
+                OsStr::new("-c"),
+                OsStr::new("echo \"$1\""),
+                &program,
+                OsStr::new("a b")
+            ]
+        );
+    }
+
+    #[test]
+    fn positional_unsupported_fails_to_spawn() {
+        let result = Sheller::new("echo hello")
+            .shell(Shell::PowerShell)
+            .positional(["a b"])
+            .try_run();
+        let Err(Error::Spawn { source, .. }) = result else {
+            panic!("expected a spawn error: {result:?}");
+        };
+        assert_eq!(source.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not support positional parameters")]
+    fn positional_unsupported_build_panics() {
+        let _ = Sheller::new("echo hello")
+            .shell(Shell::Cmd)
+            .positional(["a b"])
+            .build();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn positional_read() {
+        let stdout = Sheller::new("printf '%s|%s' \"$1\" \"$2\"")
+            .positional(["a b", "$(echo c)"])
+            .read();
+        assert_eq!(stdout, "a b|$(echo c)");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn shell_bash() {
+        let stdout = Sheller::new("echo $BASH_VERSION").shell(Shell::Bash).read();
+        assert!(!stdout.is_empty());
+    }
+
+    #[test]
+    fn program_detects_flags() {
+        let command = Sheller::new("echo hello").program("/usr/bin/zsh").build();
+        assert_eq!(command.get_program(), "/usr/bin/zsh");
+        let args = command.get_args().collect::<Vec<&OsStr>>();
+        assert_eq!(args, vec!["-c", "echo hello"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn strict_stops_on_failure() {
+        let sheller = Sheller::new("false\necho unreachable").shell(Shell::Sh);
+        assert_eq!(sheller.read(), "unreachable");
+        let sheller = Sheller::new("false\necho unreachable")
+            .shell(Shell::Sh)
+            .strict();
+        assert!(sheller.try_read().is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn strict_pipefail() {
+        let sheller = Sheller::new("false | cat").shell(Shell::Bash).strict();
+        assert!(sheller.try_run().is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn strict_preamble() {
+        let command = Sheller::new("echo hello").shell(Shell::Sh).strict().build();
+        let args = command.get_args().collect::<Vec<&OsStr>>();
+        assert_eq!(args, vec!["-c", "set -eu\necho hello"]);
+    }
+
+    #[test]
+    fn error_display_includes_command_and_stderr() {
+        let error = new!("echo oops 1>&2&& exit 3").try_output().unwrap_err();
+        assert_eq!(error.phase(), Some(sheller::Phase::Exit));
+        let message = error.to_string();
+        assert!(message.contains("exit 3"), "{message}");
+        assert!(message.contains("exited with code 3"), "{message}");
+        assert!(message.ends_with("stderr:\noops"), "{message}");
+    }
+
+    #[test]
+    fn error_display_skips_empty_stderr() {
+        let error = new!("exit 3").try_output().unwrap_err();
+        assert_eq!(error.context().unwrap().stderr_tail.as_deref(), Some(""));
+        let message = error.to_string();
+        assert!(message.contains("exited with code 3"), "{message}");
+        assert!(!message.contains("stderr:"), "{message}");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn error_stderr_tail_keeps_last_lines() {
+        let error = new!("for i in $(seq 1 30); do echo $i >&2; done; exit 1")
+            .try_output()
+            .unwrap_err();
+        let stderr_tail = error.context().unwrap().stderr_tail.as_deref().unwrap();
+        assert!(stderr_tail.starts_with("...\n11\n"), "{stderr_tail}");
+        assert!(stderr_tail.ends_with("\n30"), "{stderr_tail}");
+    }
+
+    #[test]
+    fn error_spawn_has_io_source() {
+        use std::error::Error as _;
+
+        let error = Sheller::new("echo hello")
+            .program("/nonexistent/sh")
+            .try_run()
+            .unwrap_err();
+        assert_eq!(error.phase(), Some(sheller::Phase::Spawn));
+        assert!(matches!(error, sheller::Error::Spawn { .. }));
+        let source = error.source().unwrap();
+        assert!(source.downcast_ref::<std::io::Error>().is_some());
+        assert!(error.to_string().contains("/nonexistent/sh"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn error_signal_is_named() {
+        let error = new!("kill -TERM $$").try_run().unwrap_err();
+        let sheller::Error::Signal { signal, .. } = &error else {
+            panic!("expected a signal error");
+        };
+        assert_eq!(signal.name(), Some("SIGTERM"));
+        assert!(!signal.is_oom_kill_likely());
+        assert!(error.to_string().contains("terminated by SIGTERM"));
+    }
+
+    #[test]
+    fn ok_codes_accepts_listed_codes() {
+        let code = new!("exit 1").ok_codes([0, 1]).try_run_code().unwrap();
+        assert_eq!(code, 1);
+        let error = new!("exit 2").ok_codes([0, 1]).try_run().unwrap_err();
+        assert!(matches!(error, sheller::Error::ExitCode { code: 2, .. }));
+    }
+
+    #[test]
+    fn ok_codes_applies_to_output_and_spawn() {
+        let output = new!("exit 1").ok_codes([1]).try_output().unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let mut handle = new!("exit 1").ok_codes([0, 1]).spawn().unwrap();
+        assert_eq!(handle.wait().unwrap().code(), Some(1));
+    }
+
+    #[test]
+    fn try_run_map_translates_codes() {
+        let result = new!("exit 3").try_run_map(|code| (code == 1).then_some("differ"));
+        assert!(matches!(
+            result,
+            Err(sheller::Error::ExitCode { code: 3, .. })
+        ));
+        let value = new!("exit 1")
+            .try_run_map(|code| (code == 1).then_some("differ"))
+            .unwrap();
+        assert_eq!(value, "differ");
+    }
+
+    #[test]
+    fn try_run_map_rejecting_zero_logs_neutral_message() {
+        let logs = capture_logs(|| {
+            new!("exit 0").try_run_map(|_| None::<()>).unwrap_err();
+        });
+        assert!(
+            logs.contains("Failed to run command with exit code not accepted."),
+            "{logs}"
+        );
+        assert!(!logs.contains("non-zero"), "{logs}");
+    }
+
+    #[test]
+    fn command_ext_try_run_accepting() {
+        let mut command = new!("exit 1").build();
+        assert_eq!(command.try_run_accepting(&[0, 1]).unwrap(), 1);
+        assert!(command.try_run_accepting(&[0]).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn on_stdout_line_receives_lines_in_order() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&lines);
+        new!("printf 'one\\r\\ntwo\\nthree'")
+            .on_stdout_line(move |line| sink.lock().unwrap().push(line.to_string()))
+            .try_run()
+            .unwrap();
+        assert_eq!(*lines.lock().unwrap(), ["one", "two", "three"]);
+    }
+
+    #[test]
+    fn on_stdout_line_with_try_output_still_captures() {
+        let count = Arc::new(Mutex::new(0));
+        let sink = Arc::clone(&count);
+        let output = new!("echo hello")
+            .on_stdout_line(move |_| *sink.lock().unwrap() += 1)
+            .try_output()
+            .unwrap();
+        assert_eq!(output.stdout_string().unwrap(), "hello");
+        assert_eq!(*count.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn on_stderr_line_with_spawn() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&lines);
+        let mut handle = new!("echo oops 1>&2")
+            .on_stderr_line(move |line| sink.lock().unwrap().push(line.trim_end().to_string()))
+            .spawn()
+            .unwrap();
+        handle.wait().unwrap();
+        assert_eq!(*lines.lock().unwrap(), ["oops"]);
+    }
+
+    /// A directory under the temp directory that is unique to the test process, and removed when it is dropped.
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("sheller-{}-{name}", std::process::id()));
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Run `f` in a new process of this test binary, as the test named `test`, and return what it wrote to stderr.
+    fn stderr_of(test: &str, f: impl FnOnce()) -> String {
+        if std::env::var_os("SHELLER_TEST_CHILD").is_some() {
+            f();
+            return String::new();
+        }
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", test, "--nocapture"])
+            .env("SHELLER_TEST_CHILD", "1")
+            .output()
+            .unwrap();
+        String::from_utf8(output.stderr).unwrap()
+    }
+
+    /// Collect everything logged while `f` runs, formatted without colors.
+    fn capture_logs(f: impl FnOnce()) -> String {
+        #[derive(Clone, Default)]
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Buffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, f);
+        let logs = buffer.0.lock().unwrap();
+        String::from_utf8_lossy(&logs).into_owned()
+    }
+
+    #[test]
+    fn log_output_forwards_lines_to_tracing() {
+        let logs = capture_logs(|| {
+            new!("echo hello&& echo oops 1>&2").log_output().run();
+        });
+        let output = |text| {
+            logs.lines()
+                .find(|l| l.contains(&format!("sheller::output: {text}")))
+                .unwrap()
+        };
+        let stdout = output("hello");
+        assert!(stdout.contains("INFO"), "{stdout}");
+        assert!(stdout.contains("stream=\"stdout\""), "{stdout}");
+        assert!(stdout.contains("run_id="), "{stdout}");
+        assert!(stdout.contains("pid="), "{stdout}");
+        let stderr = output("oops");
+        assert!(stderr.contains("WARN"), "{stderr}");
+    }
+
+    #[test]
+    fn run_span_records_pid_duration_and_exit() {
+        let logs = capture_logs(|| {
+            new!("exit 3").try_run().unwrap_err();
+        });
+        let failed = logs
+            .lines()
+            .find(|l| l.contains("Failed to run command"))
+            .unwrap();
+        for field in [
+            "run{",
+            "program=",
+            "cwd=",
+            "pid=",
+            "duration_ms=",
+            "exit_code=3",
+        ] {
+            assert!(failed.contains(field), "{field} in {failed}");
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn run_span_records_signal() {
+        let logs = capture_logs(|| {
+            new!("kill -TERM $$").try_run().unwrap_err();
+        });
+        let failed = logs
+            .lines()
+            .find(|l| l.contains("Failed to run command"))
+            .unwrap();
+        assert!(failed.contains("signal=SIGTERM"), "{failed}");
+    }
+
+    #[test]
+    fn log_output_levels_are_configurable() {
+        let logs = capture_logs(|| {
+            new!("echo hello")
+                .log_output_levels(sheller::Level::Debug, sheller::Level::Error)
+                .run();
+        });
+        let stdout = logs
+            .lines()
+            .find(|l| l.contains("sheller::output: hello"))
+            .unwrap();
+        assert!(stdout.contains("DEBUG"), "{stdout}");
+    }
+
+    #[test]
+    fn secret_is_redacted() {
+        let token = "hunter2";
+        let mut error = None;
+        let logs = capture_logs(|| {
+            error = new!("echo {} 1>&2 && exit 1", token)
+                .secret(token)
+                .env_secret("API_KEY", "swordfish")
+                .log_output()
+                .try_output()
+                .err();
+        });
+        let error = error.unwrap().to_string();
+        for text in [&logs, &error] {
+            assert!(!text.contains(token), "{text}");
+            assert!(!text.contains("swordfish"), "{text}");
+            assert!(text.contains("***"), "{text}");
+        }
+    }
+
+    #[test]
+    fn secret_is_passed_verbatim() {
+        let stdout = new!("echo {}", "hunter2").secret("hunter2").read();
+        assert_eq!(stdout, "hunter2");
+        let sheller = new!("echo {}", "hunter2").secret("hunter2");
+        assert!(!format!("{sheller:?}").contains("hunter2"));
+    }
+
+    #[test]
+    fn sheller_debug() {
+        let dir = TempDir::new("debug");
+        let path = dir.0.join("out.log");
+        let sheller = Sheller::new("echo")
+            .positional(["a"])
+            .env_secret("K", "swordfish")
+            .stdout_to_file(&path)
+            .stdin_bytes(vec![b'x'; 1000]);
+        let debug = format!("{sheller:?}");
+        assert!(debug.contains(r#"positional: ["a"],"#), "{debug}");
+        assert!(debug.contains(r#"envs: [("K", "***")],"#), "{debug}");
+        assert!(debug.contains("out.log"), "{debug}");
+        assert_eq!(debug.matches("out.log").count(), 1, "{debug}");
+        assert!(debug.contains("< [1000 bytes]"), "{debug}");
+        assert!(!debug.contains("swordfish"), "{debug}");
+        assert!(!debug.contains("120, 120"), "{debug}");
+    }
+
+    #[test]
+    fn pipeline_read() {
+        let stdout = Pipeline::new()
+            .sheller(new!("echo b&& echo a"))
+            .sheller(new!("sort"))
+            .read();
+        assert_eq!(
+            stdout,
+            format!("a{}b", if cfg!(windows) { "\r\n" } else { "\n" })
+        );
+    }
+
+    #[test]
+    fn pipeline_fails_with_rightmost_failure() {
+        let result = Pipeline::new()
+            .sheller(new!("exit 2"))
+            .sheller(new!("exit 3"))
+            .sheller(new!("exit 0"))
+            .try_run();
+        assert!(matches!(result, Err(Error::ExitCode { code: 3, .. })));
+    }
+
+    #[test]
+    fn pipeline_stage_results() {
+        let results = Pipeline::new()
+            .sheller(new!("exit 2"))
+            .sheller(new!("exit 3").ok_codes([3]))
+            .command(std::process::Command::new("sheller-does-not-exist"))
+            .try_run_stages();
+        assert!(matches!(results[0], Err(Error::ExitCode { code: 2, .. })));
+        assert_eq!(results[1].as_ref().unwrap(), &3);
+        assert!(matches!(results[2], Err(Error::Spawn { .. })));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn pipeline_closed_pipe() {
+        let results = Pipeline::new()
+            .sheller(new!("yes"))
+            .sheller(new!("head -n 1"))
+            .try_run_stages();
+        let Err(Error::Signal { signal, .. }) = &results[0] else {
+            panic!("{results:?}");
+        };
+        assert_eq!(signal.name(), Some("SIGPIPE"));
+        assert!(results[1].is_ok());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn command_ext_try_output_leaves_stdio() {
+        let dir = TempDir::new("try-output-stdio");
+        let path = dir.0.join("stdout");
+        let mut command = std::process::Command::new("sh");
+        command.args([
+            "-c",
+            "stdout=$(readlink /proc/$$/fd/1); echo \"$stdout\" > \"$1\"",
+            "sh",
+        ]);
+        command.arg(&path);
+        command.try_output().unwrap();
+        command.run();
+        let stdout = std::fs::read_to_string(&path).unwrap();
+        let expected = std::fs::read_link("/proc/self/fd/1").unwrap();
+        assert_eq!(stdout.trim_end(), expected.to_str().unwrap());
+    }
+
+    #[test]
+    fn redirect_stdout_and_stderr_to_file() {
+        let dir = TempDir::new("redirect");
+        let path = dir.0.join("redirect.log");
+        let logs = capture_logs(|| {
+            new!("echo hello&& echo oops 1>&2")
+                .stdout_to_file(&path)
+                .stderr_to_stdout()
+                .run();
+        });
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().collect::<Vec<_>>(), ["hello", "oops"]);
+        let running = logs
+            .lines()
+            .find(|l| l.contains("Running command."))
+            .unwrap();
+        assert!(running.contains("2>&1"), "{running}");
+        assert!(running.contains("redirect.log"), "{running}");
+    }
+
+    #[test]
+    fn redirect_stderr_into_captured_stdout() {
+        let output = new!("echo hello&& echo oops 1>&2")
+            .stderr_to_stdout()
+            .try_output()
+            .unwrap();
+        let stdout = output.stdout_string().unwrap();
+        assert_eq!(stdout.lines().collect::<Vec<_>>(), ["hello", "oops"]);
+        assert!(output.stderr.is_empty());
+    }
+
+    #[test]
+    fn redirect_missing_stdin_file() {
+        let result = new!("echo hello")
+            .stdin_from_file("sheller-does-not-exist.txt")
+            .try_run();
+        assert!(matches!(result, Err(Error::Spawn { .. })));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn redirect_stdin_from_bytes_not_read() {
+        let bytes = vec![b'a'; 1 << 20];
+        let stdout = new!("head -c 3").stdin_from_bytes(bytes).read();
+        assert_eq!(stdout, "aaa");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn command_ext_stderr_to_stdout() {
+        let dir = TempDir::new("command-redirect");
+        let path = dir.0.join("redirect.log");
+        let mut command = std::process::Command::new("sh");
+        command.args(["-c", "echo hello&& echo oops 1>&2"]);
+        let logs = capture_logs(|| {
+            command
+                .stdout_to_file(&path)
+                .unwrap()
+                .stderr_to_stdout()
+                .run();
+        });
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().collect::<Vec<_>>(), ["hello", "oops"]);
+        assert!(
+            logs.contains("Redirecting stderr of command to its stdout."),
+            "{logs}"
+        );
+
+        let mut command = std::process::Command::new("sh");
+        let output = command
+            .args(["-c", "echo hello&& echo oops 1>&2"])
+            .stderr_to_stdout()
+            .try_output()
+            .unwrap();
+        let stdout = output.stdout_string().unwrap();
+        assert_eq!(stdout.lines().collect::<Vec<_>>(), ["hello", "oops"]);
+        assert!(output.stderr.is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn command_ext_stdin_from_bytes() {
+        let mut command = std::process::Command::new("cat");
+        let stdout = command.stdin_from_bytes("hello").unwrap().read();
+        assert_eq!(stdout, "hello");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn stdin_bytes_large_output() {
+        let bytes = vec![b'a'; 1 << 20];
+        let output = new!("cat").stdin_bytes(bytes.clone()).try_output().unwrap();
+        assert_eq!(output.stdout, bytes);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn stdin_reader_not_read() {
+        let stdout = new!("head -c 3").stdin_reader(std::io::repeat(b'a')).read();
+        assert_eq!(stdout, "aaa");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn stdin_reader_error() {
+        struct Failing;
+
+        impl std::io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("broken reader"))
+            }
+        }
+
+        let result = new!("cat").stdin_reader(Failing).try_run();
+        assert!(matches!(result, Err(Error::Wait { .. })), "{result:?}");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn stdin_lines() {
+        let stdout = new!("wc -l").stdin_lines(1..=100).read();
+        assert_eq!(stdout, "100");
+    }
+
+    #[test]
+    fn tee_attaches_output() {
+        let error = new!("echo hello&& echo oops 1>&2&& exit 1")
+            .tee()
+            .try_run()
+            .unwrap_err();
+        let context = error.context().unwrap();
+        assert_eq!(
+            context.stdout_tail.as_deref().map(str::trim_end),
+            Some("hello")
+        );
+        assert_eq!(
+            context.stderr_tail.as_deref().map(str::trim_end),
+            Some("oops")
+        );
+        let message = error.to_string();
+        assert!(message.contains("stdout:\nhello"), "{message}");
+        assert!(message.contains("stderr:\noops"), "{message}");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn tee_bounded_keeps_last_bytes() {
+        let error = new!("seq 1 20; exit 1")
+            .tee_bounded(6)
+            .try_run()
+            .unwrap_err();
+        let context = error.context().unwrap();
+        assert_eq!(context.stdout_tail.as_deref(), Some("19\n20\n"));
+    }
+
+    #[test]
+    fn tee_with_line_callback() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&lines);
+        new!("echo hello")
+            .tee()
+            .on_stdout_line(move |line| sink.lock().unwrap().push(line.to_string()))
+            .run();
+        assert_eq!(*lines.lock().unwrap(), ["hello"]);
+    }
+
+    #[test]
+    fn quiet_unless_failed_with_line_callback() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&lines);
+        new!("echo hello&& echo oops 1>&2")
+            .quiet_unless_failed()
+            .on_stderr_line(move |line| sink.lock().unwrap().push(line.to_string()))
+            .run();
+        assert_eq!(*lines.lock().unwrap(), ["oops"]);
+    }
+
+    #[test]
+    fn quiet_unless_failed_failure() {
+        let result = new!("echo hello&& exit 3").quiet_unless_failed().try_run();
+        assert!(
+            matches!(result, Err(Error::ExitCode { code: 3, .. })),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn quiet_unless_failed_replays_in_order() {
+        let stderr = stderr_of("tests::quiet_unless_failed_replays_in_order", || {
+            new!("echo one; sleep 0.1; echo two 1>&2; sleep 0.1; echo three; exit 1")
+                .quiet_unless_failed()
+                .try_output()
+                .unwrap_err();
+            new!("echo four; sleep 10")
+                .quiet_unless_failed()
+                .timeout(Duration::from_millis(200))
+                .try_run()
+                .unwrap_err();
+        });
+        assert!(stderr.contains("one\ntwo\nthree\n"), "{stderr}");
+        assert!(stderr.contains("four\n"), "{stderr}");
+    }
+
+    #[test]
+    fn quiet_unless_failed_in_pipeline() {
+        let stdout = Pipeline::new()
+            .sheller(new!("echo hello").quiet_unless_failed())
+            .sheller(new!("sort").quiet_unless_failed())
+            .read();
+        assert_eq!(stdout, "hello");
+    }
+
+    #[test]
+    fn log_levels_silent() {
+        let logs = capture_logs(|| {
+            new!("exit 3")
+                .log_levels(sheller::LogLevels::SILENT)
+                .try_run()
+                .unwrap_err();
+        });
+        assert!(!logs.contains("Running command."), "{logs}");
+        assert!(!logs.contains("Failed to run command"), "{logs}");
+    }
+
+    #[test]
+    fn log_levels_failure_as_warning() {
+        let logs = capture_logs(|| {
+            new!("exit 3")
+                .log_levels(sheller::LogLevels {
+                    failure: Some(sheller::Level::Warn),
+                    ..sheller::LogLevels::DEFAULT
+                })
+                .try_run()
+                .unwrap_err();
+        });
+        let failed = logs
+            .lines()
+            .find(|l| l.contains("Failed to run command"))
+            .unwrap();
+        assert!(failed.contains("WARN"), "{failed}");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn timeout_graceful() {
+        let error = new!("sleep 10")
+            .timeout(Duration::from_millis(100))
+            .try_run()
+            .unwrap_err();
+        let sheller::Error::Timeout {
+            elapsed, forced, ..
+        } = error
+        else {
+            panic!("expected a timeout error");
+        };
+        assert!(!forced);
+        assert!(elapsed < Duration::from_secs(5));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn timeout_forced() {
+        let error = new!("trap '' TERM; sleep 10")
+            .shell(Shell::Sh)
+            .timeout(Duration::from_millis(100))
+            .grace_period(Duration::from_millis(100))
+            .try_output()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            sheller::Error::Timeout { forced: true, .. }
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn timeout_attaches_output() {
+        let error = new!("echo hello; echo oops 1>&2; sleep 10")
+            .tee()
+            .timeout(Duration::from_millis(200))
+            .try_run()
+            .unwrap_err();
+        assert!(matches!(error, sheller::Error::Timeout { .. }));
+        let context = error.context().unwrap();
+        assert_eq!(
+            context.stdout_tail.as_deref().map(str::trim_end),
+            Some("hello")
+        );
+        assert_eq!(
+            context.stderr_tail.as_deref().map(str::trim_end),
+            Some("oops")
+        );
+
+        let error = new!("echo oops 1>&2; sleep 10")
+            .timeout(Duration::from_millis(200))
+            .try_output()
+            .unwrap_err();
+        let context = error.context().unwrap();
+        assert_eq!(context.stderr_tail.as_deref(), Some("oops"));
+
+        let mut handle = new!("echo hello; sleep 10")
+            .tee()
+            .timeout(Duration::from_millis(200))
+            .spawn()
+            .unwrap();
+        let error = handle.wait().unwrap_err();
+        let context = error.context().unwrap();
+        assert_eq!(
+            context.stdout_tail.as_deref().map(str::trim_end),
+            Some("hello")
+        );
+    }
+
+    #[test]
+    fn timeout_not_reached() {
+        new!("echo hello")
+            .timeout(Duration::from_secs(60))
+            .try_run()
+            .unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn timeout_process_group_kills_grandchildren() {
+        let dir = TempDir::new("timeout-group");
+        let pid_file = dir.0.join("pid");
+        let error = Sheller::new("sleep 10 & echo $! > \"$1\"; wait")
+            .shell(Shell::Sh)
+            .positional([&pid_file])
+            .process_group()
+            .timeout(Duration::from_millis(200))
+            .try_run()
+            .unwrap_err();
+        assert!(matches!(error, sheller::Error::Timeout { .. }));
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        let state = new!("ps -o stat= -p {} || true", pid.trim()).read();
+        assert!(state.is_empty() || state.starts_with('Z'), "{state}");
+    }
+
+    #[test]
+    fn spawn_wait() {
+        let mut handle = new!("echo hello").spawn().unwrap();
+        assert!(handle.pid() > 0);
+        assert!(handle.wait().unwrap().success());
+    }
+
+    #[test]
+    fn spawn_wait_error_shows_redirect_once() {
+        let dir = TempDir::new("spawn-redirect");
+        let path = dir.0.join("out.log");
+        let mut handle = new!("exit 3").stdout_to_file(&path).spawn().unwrap();
+        let message = handle.wait().unwrap_err().to_string();
+        assert_eq!(message.matches("out.log").count(), 1, "{message}");
+    }
+
+    #[test]
+    fn spawn_wait_non_zero_exit_code() {
+        let mut handle = new!("exit 3").spawn().unwrap();
+        assert!(matches!(
+            handle.wait(),
+            Err(sheller::Error::ExitCode { code: 3, .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn spawn_try_wait_timeout() {
+        let mut handle = new!("sleep 10")
+            .timeout(Duration::from_millis(100))
+            .spawn()
+            .unwrap();
+        let result = loop {
+            match handle.try_wait() {
+                Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+                result => break result,
+            }
+        };
+        assert!(matches!(result, Err(sheller::Error::Timeout { .. })));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn spawn_drop_kills() {
+        let handle = new!("sleep 10").spawn().unwrap();
+        let pid = handle.pid();
+        drop(handle);
+        let state = new!("ps -o stat= -p {pid} || true").read();
+        assert!(state.is_empty(), "{state}");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn spawn_drop_kills_group_left_by_exited_command() {
+        let dir = TempDir::new("drop-group");
+        let pid_file = dir.0.join("pid");
+        let mut handle = Sheller::new("sleep 10 & echo $! > \"$1\"")
+            .shell(Shell::Sh)
+            .positional([&pid_file])
+            .process_group()
+            .spawn()
+            .unwrap();
+        handle.wait().unwrap();
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        assert!(!new!("ps -o stat= -p {} || true", pid.trim())
+            .read()
+            .is_empty());
+        drop(handle);
+        std::thread::sleep(Duration::from_millis(100));
+        let state = new!("ps -o stat= -p {} || true", pid.trim()).read();
+        assert!(state.is_empty() || state.starts_with('Z'), "{state}");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn spawn_kill_kills_group_left_by_exited_command() {
+        let dir = TempDir::new("kill-group");
+        let pid_file = dir.0.join("pid");
+        let mut handle = Sheller::new("sleep 10 & echo $! > \"$1\"")
+            .shell(Shell::Sh)
+            .positional([&pid_file])
+            .process_group()
+            .spawn()
+            .unwrap();
+        handle.wait().unwrap();
+        handle.set_drop_policy(DropPolicy::Detach);
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        handle.kill().unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        let state = new!("ps -o stat= -p {} || true", pid.trim()).read();
+        assert!(state.is_empty() || state.starts_with('Z'), "{state}");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn spawn_drop_detaches() {
+        let mut handle = new!("sleep 10").spawn().unwrap();
+        handle.set_drop_policy(DropPolicy::Detach);
+        let pid = handle.pid();
+        drop(handle);
+        assert!(!new!("ps -o stat= -p {pid} || true").read().is_empty());
+        new!("kill {pid}").run();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn command_ext_spawn_managed() {
+        let mut command = std::process::Command::new("echo");
+        let mut handle = command.arg("hello").spawn_managed().unwrap();
+        handle.wait().unwrap();
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn run_async() {
+        new!("echo hello").run_async().await;
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn try_run_async_non_zero_exit_code() {
+        let result = new!("exit 3").try_run_async().await;
+        assert!(matches!(
+            result,
+            Err(sheller::Error::ExitCode { code: 3, .. })
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn output_async() {
+        let output = new!("echo hello").output_async().await.unwrap();
+        assert_eq!(output.stdout_string().unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    #[cfg(unix)]
+    async fn try_run_async_timeout() {
+        let result = new!("sleep 10")
+            .timeout(Duration::from_millis(100))
+            .try_run_async()
+            .await;
+        assert!(matches!(result, Err(sheller::Error::Timeout { .. })));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    #[cfg(unix)]
+    async fn run_async_cancelled_kills_process_group() {
+        let dir = TempDir::new("async-cancel");
+        let pid_file = dir.0.join("pid");
+        let run = Sheller::new("sleep 10 & echo $! > \"$1\"; wait")
+            .shell(Shell::Sh)
+            .positional([&pid_file])
+            .process_group()
+            .try_run_async();
+        let result = tokio::time::timeout(Duration::from_millis(300), run).await;
+        assert!(result.is_err());
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let state = new!("ps -o stat= -p {} || true", pid.trim()).read();
+        assert!(state.is_empty() || state.starts_with('Z'), "{state}");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn run_async_redirects() {
+        let dir = TempDir::new("async-redirect");
+        let path = dir.0.join("redirect.log");
+        new!("echo hello&& echo oops 1>&2")
+            .stdout_to_file(&path)
+            .stderr_to_stdout()
+            .run_async()
+            .await;
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().collect::<Vec<_>>(), ["hello", "oops"]);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn output_async_redirect_stderr_into_captured_stdout() {
+        let output = new!("echo hello&& echo oops 1>&2")
+            .stderr_to_stdout()
+            .output_async()
+            .await
+            .unwrap();
+        let stdout = output.stdout_string().unwrap();
+        assert_eq!(stdout.lines().collect::<Vec<_>>(), ["hello", "oops"]);
+        assert!(output.stderr.is_empty());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    #[cfg(unix)]
+    async fn output_async_stdin_from_file() {
+        let dir = TempDir::new("async-stdin");
+        let path = dir.0.join("stdin.txt");
+        std::fs::write(&path, "hello").unwrap();
+        let output = Sheller::new("cat")
+            .stdin_from_file(&path)
+            .output_async()
+            .await
+            .unwrap();
+        assert_eq!(output.stdout_string().unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    #[cfg(unix)]
+    async fn output_async_stdin() {
+        let output = Sheller::new("sort")
+            .stdin_lines(["b", "a"])
+            .output_async()
+            .await
+            .unwrap();
+        assert_eq!(output.stdout_string().unwrap(), "a\nb");
+
+        let bytes = vec![b'a'; 1 << 20];
+        let output = Sheller::new("head -c 3")
+            .stdin_bytes(bytes)
+            .output_async()
+            .await
+            .unwrap();
+        assert_eq!(output.stdout_string().unwrap(), "aaa");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn try_run_async_tee_with_line_callback() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&lines);
+        let error = new!("echo hello&& echo oops 1>&2&& exit 1")
+            .tee()
+            .on_stdout_line(move |line| sink.lock().unwrap().push(line.to_string()))
+            .try_run_async()
+            .await
+            .unwrap_err();
+        assert_eq!(*lines.lock().unwrap(), ["hello"]);
+        let context = error.context().unwrap();
+        assert_eq!(
+            context.stdout_tail.as_deref().map(str::trim_end),
+            Some("hello")
+        );
+        assert_eq!(
+            context.stderr_tail.as_deref().map(str::trim_end),
+            Some("oops")
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn output_async_with_line_callback_still_captures() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&lines);
+        let output = new!("echo hello&& echo oops 1>&2")
+            .on_stdout_line(move |line| sink.lock().unwrap().push(line.to_string()))
+            .output_async()
+            .await
+            .unwrap();
+        assert_eq!(*lines.lock().unwrap(), ["hello"]);
+        assert_eq!(output.stdout_string().unwrap(), "hello");
+        assert_eq!(String::from_utf8_lossy(&output.stderr).trim_end(), "oops");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    #[cfg(unix)]
+    async fn async_command_ext_try_run_async() {
+        use sheller::AsyncCommandExt;
+
+        let mut command = tokio::process::Command::new("echo");
+        command.arg("hello").try_run_async().await.unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "async-process")]
+    fn await_sheller() {
+        futures_lite::future::block_on(async {
+            new!("echo hello").await.unwrap();
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "async-process")]
+    #[cfg(unix)]
+    fn await_sheller_cancelled_kills_process_group() {
+        use std::future::IntoFuture;
+
+        let dir = TempDir::new("await-cancel");
+        let pid_file = dir.0.join("pid");
+        let mut run = Sheller::new("sleep 10 & echo $! > \"$1\"; wait")
+            .shell(Shell::Sh)
+            .positional([&pid_file])
+            .process_group()
+            .into_future();
+        let polled = futures_lite::future::block_on(futures_lite::future::poll_once(&mut run));
+        assert!(polled.is_none());
+        std::thread::sleep(Duration::from_millis(300));
+        drop(run);
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        let state = new!("ps -o stat= -p {} || true", pid.trim()).read();
+        assert!(state.is_empty() || state.starts_with('Z'), "{state}");
+    }
+
+    #[test]
+    #[cfg(feature = "async-process")]
+    fn await_sheller_redirects() {
+        let dir = TempDir::new("await-redirect");
+        let path = dir.0.join("redirect.log");
+        futures_lite::future::block_on(async {
+            new!("echo hello&& echo oops 1>&2")
+                .stdout_to_file(&path)
+                .stderr_to_stdout()
+                .await
+                .unwrap();
+        });
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().collect::<Vec<_>>(), ["hello", "oops"]);
+    }
+
+    #[test]
+    #[cfg(feature = "async-process")]
+    #[cfg(unix)]
+    fn await_sheller_stdin() {
+        let dir = TempDir::new("await-stdin");
+        let path = dir.0.join("stdin.txt");
+        futures_lite::future::block_on(async {
+            Sheller::new("cat")
+                .stdin_bytes("hello")
+                .stdout_to_file(&path)
+                .await
+                .unwrap();
+        });
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello");
+    }
+
+    #[test]
+    #[cfg(feature = "async-process")]
+    fn await_sheller_log_output() {
+        let logs = capture_logs(|| {
+            futures_lite::future::block_on(async {
+                new!("echo hello").log_output().await.unwrap();
+            });
+        });
+        assert!(logs.contains("sheller::output: hello"), "{logs}");
+    }
+
+    #[test]
+    #[cfg(feature = "async-process")]
+    #[cfg(unix)]
+    fn await_sheller_quiet_unless_failed_replays_in_order() {
+        let stderr = stderr_of(
+            "tests::await_sheller_quiet_unless_failed_replays_in_order",
+            || {
+                futures_lite::future::block_on(async {
+                    new!("echo one; sleep 0.1; echo two 1>&2; exit 1")
+                        .quiet_unless_failed()
+                        .await
+                        .unwrap_err();
+                    new!("echo three; sleep 10")
+                        .quiet_unless_failed()
+                        .timeout(Duration::from_millis(200))
+                        .await
+                        .unwrap_err();
+                });
+            },
+        );
+        assert!(stderr.contains("one\ntwo\n"), "{stderr}");
+        assert!(stderr.contains("three\n"), "{stderr}");
+    }
+
+    #[test]
+    #[cfg(feature = "async-process")]
+    fn await_sheller_non_zero_exit_code() {
+        let result = futures_lite::future::block_on(async { new!("exit 3").await });
+        assert!(matches!(
+            result,
+            Err(sheller::Error::ExitCode { code: 3, .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "async-process")]
+    #[cfg(unix)]
+    fn await_sheller_timeout() {
+        let result = futures_lite::future::block_on(async {
+            new!("sleep 10").timeout(Duration::from_millis(100)).await
+        });
+        assert!(matches!(
+            result,
+            Err(sheller::Error::Timeout { forced: false, .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "async-process")]
+    #[cfg(unix)]
+    fn async_process_command_ext_output_async() {
+        use sheller::async_process::AsyncCommandExt;
+
+        let output = futures_lite::future::block_on(async {
+            let mut command = async_process::Command::new("echo");
+            command.arg("hello").output_async().await
+        })
+        .unwrap();
+        assert_eq!(output.stdout_string().unwrap(), "hello");
+    }
 }
@@ -18,6 +18,8 @@ enum Command {
     Test {
         #[arg(short, long)]
         target: Option<String>,
+        #[arg(long)]
+        all_features: bool,
     },
     PrePush,
 }
@@ -34,7 +36,12 @@ fn fmt() {
     run!("cargo fmt -- --check");
 }
 
-fn test(target: Option<String>) {
+fn test(target: Option<String>, all_features: bool) {
+    if all_features {
+        run!("cargo test --workspace --all-features");
+        return;
+    }
+
     let Some(target) = target else {
         run!("cargo test --workspace");
         return;
@@ -47,7 +54,7 @@ fn pre_push() {
     check();
     clippy();
     fmt();
-    test(None);
+    test(None, false);
 }
 
 /// During Github Actions Workflow, when running `rustup install nightly` inside a `cargo run --package tool-dev -- init` command on a Windows platform, it will fail with the following error:
@@ -85,7 +92,10 @@ fn main() {
         Command::Check => check(),
         Command::Clippy => clippy(),
         Command::Fmt => fmt(),
-        Command::Test { target } => test(target),
+        Command::Test {
+            target,
+            all_features,
+        } => test(target, all_features),
         Command::PrePush => pre_push(),
     }
 }